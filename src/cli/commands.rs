@@ -16,9 +16,9 @@ pub enum Commands {
         #[arg(value_name = "FILE")]
         path: String,
         
-        /// Environment to use
+        /// Environment name or file (YAML, JSON or .env); repeat to layer several, later ones win
         #[arg(short, long)]
-        env: Option<String>,
+        env: Vec<String>,
         
         /// Output file for response
         #[arg(short, long)]
@@ -27,6 +27,22 @@ pub enum Commands {
         /// Verbose output
         #[arg(short, long, default_value = "false")]
         verbose: bool,
+
+        /// Number of requests to run at the same time when running a directory
+        #[arg(short, long, default_value = "1")]
+        concurrency: usize,
+
+        /// Leave unresolved {{variables}} in place instead of failing
+        #[arg(long, default_value = "false")]
+        lenient: bool,
+
+        /// Set a variable, overriding environment files, captured values and {{$env.NAME}} (repeatable)
+        #[arg(long = "var", value_name = "KEY=VALUE", value_parser = parse_key_value)]
+        vars: Vec<(String, String)>,
+
+        /// Seed for built-in random variables such as {{$uuid}}, for reproducible runs
+        #[arg(long)]
+        seed: Option<u64>,
     },
     
     /// Validate request files
@@ -42,4 +58,101 @@ pub enum Commands {
         #[arg(value_name = "DIR", default_value = ".")]
         directory: String,
     },
+
+    /// Manage environment files
+    Env {
+        #[command(subcommand)]
+        command: EnvCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum EnvCommands {
+    /// List the environments found in the environment directories
+    List,
+
+    /// Show an environment's variables, with secrets masked
+    Show {
+        /// Environment name or file
+        #[arg(value_name = "NAME")]
+        name: String,
+    },
+
+    /// Show which variables differ between two environments
+    Diff {
+        /// Environment name or file
+        #[arg(value_name = "A")]
+        left: String,
+
+        /// Environment name or file
+        #[arg(value_name = "B")]
+        right: String,
+
+        /// Key file for comparing secrets; defaults to $RPCLI_SECRET_KEY, $RPCLI_SECRET_KEY_FILE or .rpcli.key
+        #[arg(short, long)]
+        key_file: Option<String>,
+    },
+
+    /// Encrypt variables in an environment file in place as `!secret` values
+    Encrypt {
+        /// Environment file to edit
+        #[arg(value_name = "FILE")]
+        file: String,
+
+        /// Variables to encrypt; all plain variables when omitted
+        #[arg(value_name = "NAME")]
+        names: Vec<String>,
+
+        /// Key file; defaults to $RPCLI_SECRET_KEY, $RPCLI_SECRET_KEY_FILE or .rpcli.key
+        #[arg(short, long)]
+        key_file: Option<String>,
+    },
+
+    /// Decrypt `!secret` variables in an environment file in place
+    Decrypt {
+        /// Environment file to edit
+        #[arg(value_name = "FILE")]
+        file: String,
+
+        /// Variables to decrypt; all secrets when omitted
+        #[arg(value_name = "NAME")]
+        names: Vec<String>,
+
+        /// Key file; defaults to $RPCLI_SECRET_KEY, $RPCLI_SECRET_KEY_FILE or .rpcli.key
+        #[arg(short, long)]
+        key_file: Option<String>,
+    },
+
+    /// Re-encrypt every secret in the given environment files with a new key
+    Rotate {
+        /// Environment files to edit
+        #[arg(value_name = "FILE", required = true)]
+        files: Vec<String>,
+
+        /// Current key file; defaults to $RPCLI_SECRET_KEY, $RPCLI_SECRET_KEY_FILE or .rpcli.key
+        #[arg(short, long)]
+        key_file: Option<String>,
+
+        /// Key file holding the new key
+        #[arg(long)]
+        new_key_file: String,
+    },
+
+    /// Generate a new secret key file
+    Keygen {
+        /// Where to write the key
+        #[arg(value_name = "FILE", default_value = ".rpcli.key")]
+        path: String,
+
+        /// Overwrite an existing key file
+        #[arg(long, default_value = "false")]
+        force: bool,
+    },
+}
+
+fn parse_key_value(input: &str) -> Result<(String, String), String> {
+    match input.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), value.to_string())),
+        _ => Err(format!("expected KEY=VALUE, got '{}'", input)),
+    }
 }
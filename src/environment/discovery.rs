@@ -0,0 +1,162 @@
+use std::path::{Path, PathBuf};
+
+use crate::request::ValidationError;
+use crate::utils::load_and_parse_file;
+
+use super::{dotenv_environment_name, is_dotenv_file, Environment};
+
+/// Directory searched before the defaults, when set.
+pub const ENVIRONMENTS_DIR_ENV: &str = "RPCLI_ENVIRONMENTS_DIR";
+
+/// An environment file found in one of the search directories.
+#[derive(Debug, Clone, PartialEq)]
+pub struct EnvironmentEntry {
+    pub name: String,
+    pub stem: String,
+    pub path: PathBuf,
+}
+
+/// Finds environments by name so `--env dev` works without a path.
+///
+/// Directories are searched in order: `$RPCLI_ENVIRONMENTS_DIR`,
+/// `./environments`, then `rpcli/environments` in the user config dir.
+/// An environment matches on its file stem or its `name`.
+#[derive(Debug, Clone)]
+pub struct EnvironmentLocator {
+    search_dirs: Vec<PathBuf>,
+}
+
+impl Default for EnvironmentLocator {
+    fn default() -> Self {
+        let mut search_dirs = Vec::new();
+        if let Ok(dir) = std::env::var(ENVIRONMENTS_DIR_ENV) {
+            search_dirs.push(PathBuf::from(dir));
+        }
+        search_dirs.push(PathBuf::from("environments"));
+        if let Some(config_dir) = dirs::config_dir() {
+            search_dirs.push(config_dir.join("rpcli").join("environments"));
+        }
+        Self { search_dirs }
+    }
+}
+
+impl EnvironmentLocator {
+    pub fn new(search_dirs: Vec<PathBuf>) -> Self {
+        Self { search_dirs }
+    }
+
+    pub fn search_dirs(&self) -> &[PathBuf] {
+        &self.search_dirs
+    }
+
+    /// Resolves `name_or_path`: an existing file is used as is, otherwise
+    /// the first environment whose stem or name matches.
+    pub fn find(&self, name_or_path: &str) -> Result<PathBuf, ValidationError> {
+        let path = Path::new(name_or_path);
+        if path.is_file() {
+            return Ok(path.to_path_buf());
+        }
+
+        let entries = self.list();
+        entries.iter()
+            .find(|entry| entry.stem == name_or_path)
+            .or_else(|| entries.iter().find(|entry| entry.name == name_or_path))
+            .or_else(|| entries.iter().find(|entry| entry.name.eq_ignore_ascii_case(name_or_path)))
+            .map(|entry| entry.path.clone())
+            .ok_or_else(|| {
+                let searched: Vec<_> = self.search_dirs.iter().map(|dir| dir.display().to_string()).collect();
+                ValidationError::FileNotFound(format!(
+                    "environment '{}' (searched {})", name_or_path, searched.join(", ")
+                ))
+            })
+    }
+
+    /// Every environment in the search directories, sorted by stem within
+    /// each directory. A stem already found in an earlier directory hides
+    /// later ones. Files that don't parse are skipped.
+    pub fn list(&self) -> Vec<EnvironmentEntry> {
+        let mut entries: Vec<EnvironmentEntry> = Vec::new();
+
+        for dir in &self.search_dirs {
+            let Ok(read_dir) = std::fs::read_dir(dir) else { continue };
+            let mut paths: Vec<_> = read_dir
+                .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+                .filter(|path| path.is_file() && is_environment_file(path))
+                .collect();
+            paths.sort();
+
+            for path in paths {
+                let stem = environment_stem(&path);
+                if entries.iter().any(|entry| entry.stem == stem) {
+                    continue;
+                }
+                if let Ok(name) = read_environment_name(&path) {
+                    entries.push(EnvironmentEntry { name, stem, path });
+                }
+            }
+        }
+
+        entries
+    }
+}
+
+fn is_environment_file(path: &Path) -> bool {
+    is_dotenv_file(path)
+        || matches!(path.extension().and_then(|ext| ext.to_str()), Some("yaml" | "yml" | "json"))
+}
+
+fn environment_stem(path: &Path) -> String {
+    if is_dotenv_file(path) {
+        return dotenv_environment_name(path);
+    }
+    path.file_stem().and_then(|stem| stem.to_str()).unwrap_or_default().to_string()
+}
+
+/// Reads only the environment's own `name`, without following `extends`
+/// or decrypting anything.
+fn read_environment_name(path: &Path) -> Result<String, ValidationError> {
+    if is_dotenv_file(path) {
+        return Ok(dotenv_environment_name(path));
+    }
+    let environment: Environment = load_and_parse_file(path)?;
+    Ok(environment.name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::Builder as TempFileBuilder;
+
+    #[test]
+    fn test_find_and_list_environments() {
+        let project = TempFileBuilder::new().prefix("test_envs_project_").tempdir().unwrap();
+        let user = TempFileBuilder::new().prefix("test_envs_user_").tempdir().unwrap();
+        std::fs::write(project.path().join("dev.yaml"), "name: Development\nvariables:\n  a: '1'").unwrap();
+        std::fs::write(project.path().join("notes.yaml"), "- not an environment").unwrap();
+        std::fs::write(user.path().join("dev.yaml"), "name: Shadowed\n").unwrap();
+        std::fs::write(user.path().join("shared.json"), r#"{"name": "Shared"}"#).unwrap();
+        std::fs::write(user.path().join(".env.local"), "A=1\n").unwrap();
+
+        let locator = EnvironmentLocator::new(vec![project.path().to_path_buf(), user.path().to_path_buf()]);
+
+        let names: Vec<_> = locator.list().into_iter().map(|entry| (entry.stem, entry.name)).collect();
+        assert_eq!(names, vec![
+            ("dev".to_string(), "Development".to_string()),
+            ("local".to_string(), "local".to_string()),
+            ("shared".to_string(), "Shared".to_string()),
+        ]);
+
+        assert_eq!(locator.find("dev").unwrap(), project.path().join("dev.yaml"));
+        assert_eq!(locator.find("development").unwrap(), project.path().join("dev.yaml"));
+        assert_eq!(locator.find("Shared").unwrap(), user.path().join("shared.json"));
+        assert_eq!(locator.find("local").unwrap(), user.path().join(".env.local"));
+
+        let explicit = user.path().join("dev.yaml");
+        assert_eq!(locator.find(explicit.to_str().unwrap()).unwrap(), explicit);
+
+        match locator.find("prod") {
+            Err(ValidationError::FileNotFound(message)) => assert!(message.starts_with("environment 'prod'")),
+            other => panic!("Expected not found, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,262 @@
+use regex::Regex;
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::request::ValidationError;
+
+use super::{Environment, VariableValue, SECRET_TAG};
+
+/// True for `.env`, `.env.<name>` and `<name>.env` files.
+pub fn is_dotenv_file<P: AsRef<Path>>(path: P) -> bool {
+    let path = path.as_ref();
+    let file_name = path.file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    file_name == ".env"
+        || file_name.starts_with(".env.")
+        || path.extension().and_then(|ext| ext.to_str()) == Some("env")
+}
+
+/// The environment name for a dotenv file: `dev` for `.env.dev` and
+/// `dev.env`, `default` for a bare `.env`.
+pub fn dotenv_environment_name<P: AsRef<Path>>(path: P) -> String {
+    let file_name = path.as_ref().file_name().and_then(|name| name.to_str()).unwrap_or_default();
+    let name = file_name.strip_prefix(".env.")
+        .or_else(|| file_name.strip_suffix(".env"))
+        .unwrap_or_default();
+    if name.is_empty() { "default".to_string() } else { name.to_string() }
+}
+
+impl Environment {
+    /// Reads a dotenv file as an environment named after the file.
+    pub fn from_dotenv<P: AsRef<Path>>(path: P) -> Result<Environment, ValidationError> {
+        let path = path.as_ref();
+        let content = crate::utils::read_file(path)?;
+        let variables = parse_dotenv(&content)
+            .map_err(|e| ValidationError::Parse(format!("{}: {}", path.display(), e)))?;
+
+        Ok(Environment {
+            name: dotenv_environment_name(path),
+            extends: None,
+            variables: Some(variables.into_iter().collect()),
+        })
+    }
+}
+
+/// One `KEY=value` entry of a dotenv file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DotenvEntry {
+    pub key: String,
+    /// The value without quotes, escapes or its comment, expanded when asked.
+    pub value: String,
+    /// True when the line starts with `export`.
+    pub export: bool,
+    /// A trailing `# comment`, with the whitespace before it.
+    pub comment: String,
+    /// The zero-based lines the entry spans; a quoted value can run on.
+    pub lines: Range<usize>,
+}
+
+/// Parses dotenv syntax: `KEY=value` lines with an optional `export`
+/// prefix, `#` comments, single quotes (literal), double quotes (escapes and
+/// expansion, may span lines) and `${VAR}` / `$VAR` expansion from earlier
+/// keys in the file, then the process environment. `${VAR:-fallback}` is
+/// supported; unknown variables expand to an empty string. An unquoted
+/// `!secret <ciphertext>` is kept encrypted (see `VariableValue`).
+pub fn parse_dotenv(content: &str) -> Result<Vec<(String, String)>, String> {
+    let mut variables: Vec<(String, String)> = Vec::new();
+    for entry in parse_dotenv_entries(content, true)? {
+        variables.retain(|(existing, _)| *existing != entry.key);
+        variables.push((entry.key, entry.value));
+    }
+    Ok(variables)
+}
+
+/// Parses every entry of a dotenv file like `parse_dotenv`. Without
+/// `expand`, `${VAR}` and `$VAR` references are left as written.
+pub fn parse_dotenv_entries(content: &str, expand: bool) -> Result<Vec<DotenvEntry>, String> {
+    let key_pattern = Regex::new(r"^(export\s+)?([A-Za-z_][A-Za-z0-9_.]*)\s*=\s*(.*)$").unwrap(); // Infallible regex
+    let mut entries: Vec<DotenvEntry> = Vec::new();
+    let mut known: HashMap<String, String> = HashMap::new();
+    let mut lines = content.lines().enumerate();
+
+    while let Some((index, line)) = lines.next() {
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        let caps = key_pattern.captures(trimmed)
+            .ok_or_else(|| format!("line {}: expected KEY=value, got '{}'", index + 1, trimmed))?;
+        let key = caps[2].to_string();
+        let raw = caps[3].to_string();
+        let mut last_line = index;
+
+        let (value, comment) = match raw.chars().next() {
+            Some(quote @ ('\'' | '"')) => {
+                let mut quoted = raw[1..].to_string();
+                let mut end = closing_quote(&quoted, quote);
+                while end.is_none() {
+                    let (next_index, next) = lines.next()
+                        .ok_or_else(|| format!("line {}: unterminated {} quote", index + 1, quote))?;
+                    quoted.push('\n');
+                    quoted.push_str(next);
+                    last_line = next_index;
+                    end = closing_quote(&quoted, quote);
+                }
+                let end = end.unwrap_or_default();
+                let inner = &quoted[..end];
+                let rest = &quoted[end + quote.len_utf8()..];
+                let comment = if rest.trim_start().starts_with('#') { rest.to_string() } else { String::new() };
+                let value = match quote {
+                    '\'' => inner.to_string(),
+                    _ if expand => expand_references(&unescape(inner), &known),
+                    _ => unescape(inner).replace("\\$", "$"),
+                };
+                (value, comment)
+            }
+            _ => {
+                let (unquoted, comment) = match raw.find(" #").or_else(|| raw.find("\t#")) {
+                    Some(comment) => raw.split_at(comment),
+                    None => (raw.as_str(), ""),
+                };
+                let unquoted = unquoted.trim();
+                let value = match unquoted.strip_prefix(SECRET_TAG).filter(|rest| rest.starts_with(' ')) {
+                    Some(ciphertext) => VariableValue::Secret(ciphertext.trim().to_string()).to_raw(),
+                    None if expand => expand_references(unquoted, &known),
+                    None => unquoted.replace("\\$", "$"),
+                };
+                (value, comment.to_string())
+            }
+        };
+
+        known.insert(key.clone(), value.clone());
+        entries.push(DotenvEntry {
+            key,
+            value,
+            export: caps.get(1).is_some(),
+            comment,
+            lines: index..last_line + 1,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Byte offset of the first unescaped `quote` in `text`.
+fn closing_quote(text: &str, quote: char) -> Option<usize> {
+    let mut escaped = false;
+    for (offset, c) in text.char_indices() {
+        match c {
+            '\\' if quote == '"' && !escaped => escaped = true,
+            c if c == quote && !escaped => return Some(offset),
+            _ => escaped = false,
+        }
+    }
+    None
+}
+
+fn unescape(text: &str) -> String {
+    let mut result = String::with_capacity(text.len());
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            // Keep `\$` escaped so expansion leaves it alone.
+            Some('$') => result.push_str("\\$"),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+fn expand_references(text: &str, known: &HashMap<String, String>) -> String {
+    let reference = Regex::new(r"\\\$|\$\{([A-Za-z_][A-Za-z0-9_.]*)(?::-([^}]*))?\}|\$([A-Za-z_][A-Za-z0-9_]*)").unwrap(); // Infallible regex
+
+    reference.replace_all(text, |caps: &regex::Captures| {
+        if &caps[0] == "\\$" {
+            return "$".to_string();
+        }
+        let name = caps.get(1).or_else(|| caps.get(3)).map(|m| m.as_str()).unwrap_or_default();
+        let value = known.get(name).cloned().or_else(|| std::env::var(name).ok());
+        match (value, caps.get(2)) {
+            (Some(value), _) if !value.is_empty() => value,
+            (_, Some(fallback)) => fallback.as_str().to_string(),
+            (value, None) => value.unwrap_or_default(),
+        }
+    }).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dotenv_syntax() {
+        let content = r#"
+# Service settings
+export HOST=api.example.com
+PORT = 8080 # inline comment
+BASE_URL=https://${HOST}:$PORT/v1
+LITERAL='${HOST} # not a comment'
+GREETING="Hello\t\"world\"\nfrom ${HOST}"
+PRICE="\$5"
+REGION=${RUSTMAN_TEST_UNSET_REGION:-eu-west-1}
+MULTI="line one
+line two"
+EMPTY=
+"#;
+        let variables: HashMap<_, _> = parse_dotenv(content).unwrap().into_iter().collect();
+
+        assert_eq!(variables["HOST"], "api.example.com");
+        assert_eq!(variables["PORT"], "8080");
+        assert_eq!(variables["BASE_URL"], "https://api.example.com:8080/v1");
+        assert_eq!(variables["LITERAL"], "${HOST} # not a comment");
+        assert_eq!(variables["GREETING"], "Hello\t\"world\"\nfrom api.example.com");
+        assert_eq!(variables["PRICE"], "$5");
+        assert_eq!(variables["REGION"], "eu-west-1");
+        assert_eq!(variables["MULTI"], "line one\nline two");
+        assert_eq!(variables["EMPTY"], "");
+    }
+
+    #[test]
+    fn test_parse_dotenv_entries_without_expansion() {
+        let content = "HOST=example.com\nexport URL=\"https://${HOST}/\\$x\" # api\nMULTI=\"a\nb\"\n";
+        let entries = parse_dotenv_entries(content, false).unwrap();
+
+        assert_eq!(entries[1], DotenvEntry {
+            key: "URL".to_string(),
+            value: "https://${HOST}/$x".to_string(),
+            export: true,
+            comment: " # api".to_string(),
+            lines: 1..2,
+        });
+        assert_eq!(entries[2].value, "a\nb");
+        assert_eq!(entries[2].lines, 2..4);
+    }
+
+    #[test]
+    fn test_parse_dotenv_errors() {
+        assert!(parse_dotenv("JUST_A_WORD").unwrap_err().contains("line 1"));
+        assert!(parse_dotenv("A=1\nB=\"open").unwrap_err().contains("unterminated"));
+    }
+
+    #[test]
+    fn test_dotenv_file_names() {
+        assert!(is_dotenv_file(".env"));
+        assert!(is_dotenv_file("config/.env.staging"));
+        assert!(is_dotenv_file("prod.env"));
+        assert!(!is_dotenv_file("dev.yaml"));
+        assert!(!is_dotenv_file(".envrc"));
+
+        assert_eq!(dotenv_environment_name(".env"), "default");
+        assert_eq!(dotenv_environment_name("config/.env.staging"), "staging");
+        assert_eq!(dotenv_environment_name("prod.env"), "prod");
+    }
+}
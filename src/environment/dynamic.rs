@@ -0,0 +1,156 @@
+use chrono::{DateTime, SecondsFormat, TimeDelta, Utc};
+use rand::distributions::Alphanumeric;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::sync::{Arc, Mutex};
+
+/// Generates the built-in `{{$name}}` variables. Every placeholder gets a
+/// fresh value; seeding makes the sequence reproducible.
+///
+/// | Placeholder         | Value                                      |
+/// |---------------------|--------------------------------------------|
+/// | `$uuid`             | random v4 UUID                             |
+/// | `$timestamp`        | Unix time in seconds                       |
+/// | `$isoTimestamp`     | current time, RFC 3339 with milliseconds   |
+/// | `$randomInt`        | integer between 0 and 1000                 |
+/// | `$randomString`     | 16 alphanumeric characters                 |
+/// | `$now`, `$now +1d`  | current time shifted by `s`/`m`/`h`/`d`/`w` |
+#[derive(Debug, Clone)]
+pub struct DynamicVariables {
+    rng: Arc<Mutex<StdRng>>,
+    fixed_now: Option<DateTime<Utc>>,
+}
+
+impl Default for DynamicVariables {
+    fn default() -> Self {
+        Self {
+            rng: Arc::new(Mutex::new(StdRng::from_entropy())),
+            fixed_now: None,
+        }
+    }
+}
+
+impl DynamicVariables {
+    pub fn with_seed(seed: u64) -> Self {
+        Self {
+            rng: Arc::new(Mutex::new(StdRng::seed_from_u64(seed))),
+            fixed_now: None,
+        }
+    }
+
+    /// Pins the clock used by the time-based variables.
+    pub fn with_fixed_now(mut self, now: DateTime<Utc>) -> Self {
+        self.fixed_now = Some(now);
+        self
+    }
+
+    /// True when `placeholder` (the text between the braces) names a built-in variable.
+    pub fn recognizes(placeholder: &str) -> bool {
+        matches!(
+            placeholder,
+            "$uuid" | "$timestamp" | "$isoTimestamp" | "$randomInt" | "$randomString"
+        ) || Self::parse_now(placeholder).is_some()
+    }
+
+    pub fn generate(&self, placeholder: &str) -> Option<String> {
+        match placeholder {
+            "$uuid" => {
+                let bytes: [u8; 16] = self.with_rng(|rng| rng.gen());
+                Some(uuid::Builder::from_random_bytes(bytes).into_uuid().to_string())
+            }
+            "$timestamp" => Some(self.now().timestamp().to_string()),
+            "$isoTimestamp" => Some(self.now().to_rfc3339_opts(SecondsFormat::Millis, true)),
+            "$randomInt" => Some(self.with_rng(|rng| rng.gen_range(0..=1000)).to_string()),
+            "$randomString" => Some(self.with_rng(|rng| {
+                rng.sample_iter(&Alphanumeric).take(16).map(char::from).collect()
+            })),
+            _ => Self::parse_now(placeholder)
+                .and_then(|offset| self.now().checked_add_signed(offset))
+                .map(|time| time.to_rfc3339_opts(SecondsFormat::Secs, true)),
+        }
+    }
+
+    fn now(&self) -> DateTime<Utc> {
+        self.fixed_now.unwrap_or_else(Utc::now)
+    }
+
+    fn with_rng<T>(&self, f: impl FnOnce(&mut StdRng) -> T) -> T {
+        let mut rng = self.rng.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        f(&mut rng)
+    }
+
+    /// Parses `$now` and `$now +1d` style placeholders into an offset;
+    /// `None` when the offset is too large to represent.
+    fn parse_now(placeholder: &str) -> Option<TimeDelta> {
+        let rest = placeholder.strip_prefix("$now")?;
+        if rest.is_empty() {
+            return Some(TimeDelta::zero());
+        }
+
+        let offset = rest.strip_prefix(' ')?;
+        let (sign, offset) = match offset.split_at_checked(1)? {
+            ("+", offset) => (1, offset),
+            ("-", offset) => (-1, offset),
+            _ => return None,
+        };
+        let (amount, unit) = offset.split_at_checked(offset.len().checked_sub(1)?)?;
+        let amount: i64 = amount.parse().ok()?;
+        let amount = amount.checked_mul(sign)?;
+
+        match unit {
+            "s" => TimeDelta::try_seconds(amount),
+            "m" => TimeDelta::try_minutes(amount),
+            "h" => TimeDelta::try_hours(amount),
+            "d" => TimeDelta::try_days(amount),
+            "w" => TimeDelta::try_weeks(amount),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn fixed() -> DynamicVariables {
+        DynamicVariables::with_seed(42).with_fixed_now(Utc.with_ymd_and_hms(2024, 1, 31, 12, 0, 0).unwrap())
+    }
+
+    #[test]
+    fn test_seeded_values_are_reproducible_and_fresh() {
+        let first = fixed();
+        let second = fixed();
+
+        let a = first.generate("$uuid").unwrap();
+        let b = first.generate("$uuid").unwrap();
+        assert_ne!(a, b);
+        assert_eq!(a, second.generate("$uuid").unwrap());
+        assert_eq!(uuid::Uuid::parse_str(&a).unwrap().get_version_num(), 4);
+
+        let number: u32 = first.generate("$randomInt").unwrap().parse().unwrap();
+        assert!(number <= 1000);
+        let text = first.generate("$randomString").unwrap();
+        assert_eq!(text.len(), 16);
+        assert!(text.chars().all(|c| c.is_ascii_alphanumeric()));
+    }
+
+    #[test]
+    fn test_time_variables_and_offsets() {
+        let dynamic = fixed();
+        assert_eq!(dynamic.generate("$timestamp").unwrap(), "1706702400");
+        assert_eq!(dynamic.generate("$isoTimestamp").unwrap(), "2024-01-31T12:00:00.000Z");
+        assert_eq!(dynamic.generate("$now").unwrap(), "2024-01-31T12:00:00Z");
+        assert_eq!(dynamic.generate("$now +1d").unwrap(), "2024-02-01T12:00:00Z");
+        assert_eq!(dynamic.generate("$now -2h").unwrap(), "2024-01-31T10:00:00Z");
+        assert_eq!(dynamic.generate("$now +1w").unwrap(), "2024-02-07T12:00:00Z");
+        assert_eq!(dynamic.generate("$now +1y"), None);
+        assert_eq!(dynamic.generate("$nowish"), None);
+        assert!(!DynamicVariables::recognizes("$unknown"));
+
+        // Offsets beyond the representable range are unresolved, not a panic.
+        assert_eq!(dynamic.generate("$now +99999999999999d"), None);
+        assert_eq!(dynamic.generate("$now -9999999999999999s"), None);
+        assert_eq!(dynamic.generate("$now +9223372036854775807w"), None);
+    }
+}
@@ -1,2 +1,12 @@
 pub mod variables;
+pub mod dynamic;
+pub mod template;
+pub mod secrets;
+pub mod dotenv;
+pub mod discovery;
 pub use variables::*;
+pub use dynamic::*;
+pub use template::*;
+pub use secrets::*;
+pub use dotenv::*;
+pub use discovery::*;
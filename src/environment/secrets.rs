@@ -0,0 +1,511 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use regex::Regex;
+use std::path::{Path, PathBuf};
+
+use crate::request::ValidationError;
+
+use super::{is_dotenv_file, parse_dotenv_entries, DotenvEntry};
+
+/// How files mark an encrypted value: the YAML `!secret` tag, an unquoted
+/// `!secret <ciphertext>` in dotenv files and `{"!secret": "<ciphertext>"}`
+/// in JSON. A quoted string that merely starts with it stays plain text.
+pub const SECRET_TAG: &str = "!secret";
+
+/// Prefixes a still-encrypted value in `Environment::variables`. It starts
+/// with a NUL so it cannot collide with a plain value read from a file.
+pub const SECRET_PREFIX: &str = "\u{0}!secret ";
+
+/// Base64-encoded key used to decrypt `!secret` values.
+pub const SECRET_KEY_ENV: &str = "RPCLI_SECRET_KEY";
+/// Path to a file holding the base64-encoded key.
+pub const SECRET_KEY_FILE_ENV: &str = "RPCLI_SECRET_KEY_FILE";
+/// Key file used when neither variable is set, relative to the working directory.
+pub const DEFAULT_KEY_FILE: &str = ".rpcli.key";
+
+const NONCE_LEN: usize = 12;
+
+/// An AES-256-GCM key for encrypting environment values.
+///
+/// Ciphertexts are base64 of a random 12-byte nonce followed by the
+/// encrypted value and its authentication tag.
+#[derive(Clone)]
+pub struct SecretKey {
+    cipher: Aes256Gcm,
+}
+
+impl SecretKey {
+    pub fn generate() -> (Self, String) {
+        let key = Aes256Gcm::generate_key(OsRng);
+        let encoded = BASE64.encode(key);
+        (Self { cipher: Aes256Gcm::new(&key) }, encoded)
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, ValidationError> {
+        let bytes = BASE64.decode(encoded.trim())
+            .map_err(|e| ValidationError::Secret(format!("Secret key is not valid base64: {}", e)))?;
+        if bytes.len() != 32 {
+            return Err(ValidationError::Secret(format!(
+                "Secret key must be 32 bytes, got {}", bytes.len()
+            )));
+        }
+        Ok(Self { cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&bytes)) })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ValidationError> {
+        let path = path.as_ref();
+        let encoded = std::fs::read_to_string(path).map_err(|e| {
+            ValidationError::Secret(format!("Failed to read key file {}: {}", path.display(), e))
+        })?;
+        Self::from_base64(&encoded)
+    }
+
+    /// Finds the key: `RPCLI_SECRET_KEY`, then the file named by
+    /// `RPCLI_SECRET_KEY_FILE`, then `.rpcli.key` in the working directory.
+    pub fn from_env() -> Result<Self, ValidationError> {
+        if let Ok(encoded) = std::env::var(SECRET_KEY_ENV) {
+            return Self::from_base64(&encoded);
+        }
+        let path = std::env::var(SECRET_KEY_FILE_ENV)
+            .map(PathBuf::from)
+            .unwrap_or_else(|_| PathBuf::from(DEFAULT_KEY_FILE));
+        if !path.is_file() {
+            return Err(ValidationError::Secret(format!(
+                "No secret key found; set {} or {}, or create {}",
+                SECRET_KEY_ENV, SECRET_KEY_FILE_ENV, DEFAULT_KEY_FILE
+            )));
+        }
+        Self::from_file(path)
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, ValidationError> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self.cipher.encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| ValidationError::Secret("Encryption failed".to_string()))?;
+
+        let mut payload = nonce.to_vec();
+        payload.extend(ciphertext);
+        Ok(BASE64.encode(payload))
+    }
+
+    pub fn decrypt(&self, ciphertext: &str) -> Result<String, ValidationError> {
+        let payload = BASE64.decode(ciphertext.trim())
+            .map_err(|e| ValidationError::Secret(format!("Ciphertext is not valid base64: {}", e)))?;
+        if payload.len() <= NONCE_LEN {
+            return Err(ValidationError::Secret("Ciphertext is too short".to_string()));
+        }
+
+        let (nonce, encrypted) = payload.split_at(NONCE_LEN);
+        let plaintext = self.cipher.decrypt(Nonce::from_slice(nonce), encrypted)
+            .map_err(|_| ValidationError::Secret("Decryption failed; wrong key or corrupted value".to_string()))?;
+        String::from_utf8(plaintext)
+            .map_err(|_| ValidationError::Secret("Decrypted value is not valid UTF-8".to_string()))
+    }
+}
+
+/// A variable as written in an environment file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableValue {
+    Plain(String),
+    /// The ciphertext of a `!secret` value.
+    Secret(String),
+}
+
+impl VariableValue {
+    /// Reads a value from `Environment::variables`.
+    pub fn parse(raw: &str) -> Self {
+        match raw.strip_prefix(SECRET_PREFIX) {
+            Some(ciphertext) => VariableValue::Secret(ciphertext.trim().to_string()),
+            None => VariableValue::Plain(raw.to_string()),
+        }
+    }
+
+    /// The form stored in `Environment::variables` before decryption.
+    pub fn to_raw(&self) -> String {
+        match self {
+            VariableValue::Plain(value) => value.clone(),
+            VariableValue::Secret(ciphertext) => format!("{}{}", SECRET_PREFIX, ciphertext),
+        }
+    }
+}
+
+/// Rewrites the `variables` of the environment file at `path` in place,
+/// passing each one through `transform`. YAML and dotenv files are edited
+/// line by line so comments and layout survive; only changed values are rewritten.
+/// YAML `variables` written in flow style (`variables: {a: 1}`) are an error.
+/// JSON files keep their key order but are re-indented.
+/// Dotenv values are passed without expanding their `${VAR}` references.
+/// Returns how many variables changed.
+pub fn rewrite_environment_variables<P, F>(path: P, mut transform: F) -> Result<usize, ValidationError>
+where
+    P: AsRef<Path>,
+    F: FnMut(&str, VariableValue) -> Result<VariableValue, ValidationError>,
+{
+    let path = path.as_ref();
+    let content = crate::utils::read_file(path)?;
+    let is_json = path.extension().and_then(|ext| ext.to_str()) == Some("json");
+    let mut changed = 0;
+
+    let rewritten = if is_dotenv_file(path) {
+        let lines: Vec<&str> = content.lines().collect();
+        let entries = parse_dotenv_entries(&content, false)
+            .map_err(|e| ValidationError::Parse(format!("{}: {}", path.display(), e)))?;
+        let mut rewritten_lines = Vec::new();
+        let mut next_line = 0;
+
+        for entry in entries {
+            let current = VariableValue::parse(&entry.value);
+            let updated = transform(&entry.key, current.clone())?;
+            if updated == current {
+                continue;
+            }
+            rewritten_lines.extend(lines[next_line..entry.lines.start].iter().map(|line| line.to_string()));
+            rewritten_lines.push(dotenv_entry_line(&entry, &updated));
+            next_line = entry.lines.end;
+            changed += 1;
+        }
+        rewritten_lines.extend(lines[next_line..].iter().map(|line| line.to_string()));
+
+        let mut rewritten = rewritten_lines.join("\n");
+        if content.ends_with('\n') {
+            rewritten.push('\n');
+        }
+        rewritten
+    } else if is_json {
+        let mut document: serde_json::Value = serde_json::from_str(&content)
+            .map_err(|e| ValidationError::Parse(format!("{}: {}", path.display(), e)))?;
+        if let Some(variables) = document.get_mut("variables").and_then(|v| v.as_object_mut()) {
+            for (name, value) in variables.iter_mut() {
+                let current = match value {
+                    serde_json::Value::String(text) => VariableValue::Plain(text.clone()),
+                    serde_json::Value::Object(object) if object.len() == 1 => {
+                        match object.get(SECRET_TAG).and_then(|ciphertext| ciphertext.as_str()) {
+                            Some(ciphertext) => VariableValue::Secret(ciphertext.to_string()),
+                            None => continue,
+                        }
+                    }
+                    _ => continue,
+                };
+                let updated = transform(name, current.clone())?;
+                if updated != current {
+                    *value = match updated {
+                        VariableValue::Plain(text) => serde_json::Value::String(text),
+                        VariableValue::Secret(ciphertext) => serde_json::json!({ SECRET_TAG: ciphertext }),
+                    };
+                    changed += 1;
+                }
+            }
+        }
+        serde_json::to_string_pretty(&document).map_err(|e| ValidationError::Parse(e.to_string()))? + "\n"
+    } else {
+        let mut lines = Vec::new();
+        let mut in_variables = false;
+        let mut entry_indent = None;
+
+        for line in content.lines() {
+            let indent = line.len() - line.trim_start().len();
+            let trimmed = line.trim();
+
+            if indent == 0 && !trimmed.is_empty() && !trimmed.starts_with('#') {
+                in_variables = trimmed.starts_with("variables:");
+                let inline = trimmed.strip_prefix("variables:").map(|rest| rest.trim()).unwrap_or_default();
+                if !inline.is_empty() && !inline.starts_with('#') {
+                    return Err(ValidationError::Parse(format!(
+                        "{}: cannot rewrite `variables` written on one line; put each variable on its own line",
+                        path.display()
+                    )));
+                }
+                entry_indent = None;
+                lines.push(line.to_string());
+                continue;
+            }
+            if !in_variables || trimmed.is_empty() || trimmed.starts_with('#') {
+                lines.push(line.to_string());
+                continue;
+            }
+            if *entry_indent.get_or_insert(indent) != indent {
+                lines.push(line.to_string());
+                continue;
+            }
+
+            match rewrite_yaml_entry(line, indent, &mut transform)? {
+                Some(updated) => {
+                    lines.push(updated);
+                    changed += 1;
+                }
+                None => lines.push(line.to_string()),
+            }
+        }
+
+        let mut rewritten = lines.join("\n");
+        if content.ends_with('\n') {
+            rewritten.push('\n');
+        }
+        rewritten
+    };
+
+    if changed > 0 {
+        std::fs::write(path, rewritten)
+            .map_err(|e| ValidationError::FileIo(format!("Failed to write {}: {}", path.display(), e)))?;
+    }
+    Ok(changed)
+}
+
+/// Writes `entry` back as one line holding `value`. Plain text with
+/// `${VAR}` or `$VAR` references goes in double quotes so they expand again;
+/// other text is quoted so it reads back literally.
+fn dotenv_entry_line(entry: &DotenvEntry, value: &VariableValue) -> String {
+    let reference = Regex::new(r"\$\{?[A-Za-z_]").unwrap(); // Infallible regex
+    let escape = |text: &str, dollar: &str| text
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('$', dollar)
+        .replace('\n', "\\n");
+
+    let export = if entry.export { "export " } else { "" };
+    let value = match value {
+        VariableValue::Plain(text) if reference.is_match(text) => format!("\"{}\"", escape(text, "$")),
+        VariableValue::Plain(text) if !text.contains('\'') && !text.contains('\n') => format!("'{}'", text),
+        VariableValue::Plain(text) => format!("\"{}\"", escape(text, "\\$")),
+        VariableValue::Secret(ciphertext) => format!("{} {}", SECRET_TAG, ciphertext),
+    };
+    format!("{}{}={}{}", export, entry.key, value, entry.comment)
+}
+
+/// Rewrites one `name: value` line, keeping a trailing `# comment`, or
+/// returns `None` when it is unchanged or not a single-line scalar.
+fn rewrite_yaml_entry<F>(line: &str, indent: usize, transform: &mut F) -> Result<Option<String>, ValidationError>
+where
+    F: FnMut(&str, VariableValue) -> Result<VariableValue, ValidationError>,
+{
+    let Some((name, value)) = line.trim().split_once(':') else { return Ok(None) };
+    let name = name.trim().trim_matches(|c| c == '"' || c == '\'');
+    let value = value.trim();
+    if value.starts_with('|') || value.starts_with('>') {
+        return Ok(None);
+    }
+
+    let parsed = serde_yaml::from_str::<serde_yaml::Value>(value);
+    let current = match &parsed {
+        Ok(serde_yaml::Value::String(text)) => VariableValue::Plain(text.clone()),
+        Ok(serde_yaml::Value::Tagged(tagged)) if tagged.tag == "secret" => match tagged.value.as_str() {
+            Some(ciphertext) => VariableValue::Secret(ciphertext.to_string()),
+            None => return Ok(None),
+        },
+        Ok(serde_yaml::Value::Number(number)) => VariableValue::Plain(number.to_string()),
+        Ok(serde_yaml::Value::Bool(flag)) => VariableValue::Plain(flag.to_string()),
+        _ => return Ok(None),
+    };
+
+    let updated = transform(name, current.clone())?;
+    if updated == current {
+        return Ok(None);
+    }
+
+    // The comment starts at the first ` #` whose preceding text alone
+    // parses to the same value, which skips a `#` inside quotes.
+    let comment = value.match_indices(" #").chain(value.match_indices("\t#"))
+        .map(|(start, _)| start)
+        .filter(|&start| serde_yaml::from_str::<serde_yaml::Value>(&value[..start]).ok() == parsed.as_ref().ok().cloned())
+        .min()
+        .map(|start| &value[start..])
+        .unwrap_or_default();
+
+    let value = match &updated {
+        VariableValue::Plain(text) => serde_json::to_string(text).unwrap_or_default(),
+        VariableValue::Secret(ciphertext) => format!("{} {}", SECRET_TAG, ciphertext),
+    };
+    Ok(Some(format!("{}{}: {}{}", " ".repeat(indent), name, value, comment)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::environment::parse_dotenv;
+    use std::io::Write;
+    use tempfile::Builder as TempFileBuilder;
+
+    #[test]
+    fn test_encrypt_decrypt_round_trip() {
+        let (key, encoded) = SecretKey::generate();
+        let ciphertext = key.encrypt("s3cr3t").unwrap();
+        assert_ne!(ciphertext, key.encrypt("s3cr3t").unwrap(), "nonces should differ");
+        assert_eq!(key.decrypt(&ciphertext).unwrap(), "s3cr3t");
+        assert_eq!(SecretKey::from_base64(&encoded).unwrap().decrypt(&ciphertext).unwrap(), "s3cr3t");
+
+        let (other_key, _) = SecretKey::generate();
+        assert!(matches!(other_key.decrypt(&ciphertext), Err(ValidationError::Secret(_))));
+        assert!(SecretKey::from_base64("c2hvcnQ=").is_err());
+    }
+
+    #[test]
+    fn test_rewrite_yaml_keeps_comments_and_other_lines() {
+        let mut file = TempFileBuilder::new().suffix(".yaml").tempfile().unwrap();
+        write!(file, "# Dev settings\nname: Dev\nvariables:\n  # API\n  base_url: \"https://dev\"\n  auth_token: tok-123 # rotate monthly\n  retries: 3\n").unwrap();
+        file.flush().unwrap();
+
+        let (key, _) = SecretKey::generate();
+        let changed = rewrite_environment_variables(file.path(), |name, value| match value {
+            VariableValue::Plain(text) if name == "auth_token" => Ok(VariableValue::Secret(key.encrypt(&text)?)),
+            other => Ok(other),
+        }).unwrap();
+        assert_eq!(changed, 1);
+
+        let content = std::fs::read_to_string(file.path()).unwrap();
+        let lines: Vec<_> = content.lines().collect();
+        assert_eq!(lines[..5], ["# Dev settings", "name: Dev", "variables:", "  # API", "  base_url: \"https://dev\""]);
+        assert!(lines[5].starts_with("  auth_token: !secret "));
+        assert!(lines[5].ends_with(" # rotate monthly"));
+        assert_eq!(lines[6], "  retries: 3");
+
+        let changed = rewrite_environment_variables(file.path(), |_, value| match value {
+            VariableValue::Secret(ciphertext) => Ok(VariableValue::Plain(key.decrypt(&ciphertext)?)),
+            other => Ok(other),
+        }).unwrap();
+        assert_eq!(changed, 1);
+        assert!(std::fs::read_to_string(file.path()).unwrap().contains("  auth_token: \"tok-123\" # rotate monthly\n"));
+    }
+
+    #[test]
+    fn test_rewrite_dotenv() {
+        let mut file = TempFileBuilder::new().suffix(".env").tempfile().unwrap();
+        write!(file, "# Partner API\nexport API_KEY=k-123\nHOST=example.com\n").unwrap();
+        file.flush().unwrap();
+
+        let (key, _) = SecretKey::generate();
+        rewrite_environment_variables(file.path(), |name, value| match value {
+            VariableValue::Plain(text) if name == "API_KEY" => Ok(VariableValue::Secret(key.encrypt(&text)?)),
+            other => Ok(other),
+        }).unwrap();
+
+        let content = std::fs::read_to_string(file.path()).unwrap();
+        assert!(content.starts_with("# Partner API\nexport API_KEY=!secret "));
+        assert!(content.ends_with("\nHOST=example.com\n"));
+
+        rewrite_environment_variables(file.path(), |_, value| match value {
+            VariableValue::Secret(ciphertext) => Ok(VariableValue::Plain(key.decrypt(&ciphertext)?)),
+            other => Ok(other),
+        }).unwrap();
+        assert!(std::fs::read_to_string(file.path()).unwrap().contains("export API_KEY='k-123'\n"));
+    }
+
+    #[test]
+    fn test_rewrite_dotenv_round_trips_local_env() {
+        let temp_dir = TempFileBuilder::new().prefix("test_rewrite_local_").tempdir().unwrap();
+        let path = temp_dir.path().join("local.env");
+        let original = std::fs::read_to_string("examples/environments/local.env").unwrap();
+        std::fs::write(&path, &original).unwrap();
+
+        let (key, _) = SecretKey::generate();
+        let mut encrypted = Vec::new();
+        let changed = rewrite_environment_variables(&path, |name, value| match value {
+            VariableValue::Plain(text) => {
+                encrypted.push((name.to_string(), text.clone()));
+                Ok(VariableValue::Secret(key.encrypt(&text)?))
+            }
+            other => Ok(other),
+        }).unwrap();
+        assert_eq!(changed, 5);
+        assert!(encrypted.contains(&("base_url".to_string(), "http://${API_HOST}:${API_PORT}".to_string())));
+
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(content.lines().count(), original.lines().count());
+        assert!(content.contains("\nexport API_HOST=!secret "));
+
+        rewrite_environment_variables(&path, |_, value| match value {
+            VariableValue::Secret(ciphertext) => Ok(VariableValue::Plain(key.decrypt(&ciphertext)?)),
+            other => Ok(other),
+        }).unwrap();
+        let content = std::fs::read_to_string(&path).unwrap();
+        assert!(content.contains("\nbase_url=\"http://${API_HOST}:${API_PORT}\"\n"));
+        assert_eq!(parse_dotenv(&content).unwrap(), parse_dotenv(&original).unwrap());
+    }
+
+    #[test]
+    fn test_rewrite_dotenv_multi_line_and_literal_values() {
+        let mut file = TempFileBuilder::new().suffix(".env").tempfile().unwrap();
+        let original = "CERT=\"line one\nline two\" # pem\nPRICE='$5 \"net\"'\nNEXT=1\n";
+        write!(file, "{}", original).unwrap();
+        file.flush().unwrap();
+
+        let (key, _) = SecretKey::generate();
+        let changed = rewrite_environment_variables(file.path(), |name, value| match value {
+            VariableValue::Plain(text) if name != "NEXT" => Ok(VariableValue::Secret(key.encrypt(&text)?)),
+            other => Ok(other),
+        }).unwrap();
+        assert_eq!(changed, 2);
+        let lines: Vec<_> = std::fs::read_to_string(file.path()).unwrap().lines().map(String::from).collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("CERT=!secret ") && lines[0].ends_with(" # pem"));
+        assert_eq!(lines[2], "NEXT=1");
+
+        rewrite_environment_variables(file.path(), |_, value| match value {
+            VariableValue::Secret(ciphertext) => Ok(VariableValue::Plain(key.decrypt(&ciphertext)?)),
+            other => Ok(other),
+        }).unwrap();
+        let content = std::fs::read_to_string(file.path()).unwrap();
+        assert_eq!(parse_dotenv(&content).unwrap(), parse_dotenv(original).unwrap());
+    }
+
+    #[test]
+    fn test_rewrite_json_uses_secret_objects() {
+        let mut file = TempFileBuilder::new().suffix(".json").tempfile().unwrap();
+        write!(file, r#"{{"name": "Dev", "variables": {{"api_key": "k-123", "note": "!secret is just text"}}}}"#).unwrap();
+        file.flush().unwrap();
+
+        let (key, _) = SecretKey::generate();
+        let changed = rewrite_environment_variables(file.path(), |_, value| match value {
+            VariableValue::Plain(text) if text.starts_with("k-") => Ok(VariableValue::Secret(key.encrypt(&text)?)),
+            other => Ok(other),
+        }).unwrap();
+        assert_eq!(changed, 1);
+
+        let document: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(file.path()).unwrap()).unwrap();
+        let ciphertext = document["variables"]["api_key"][SECRET_TAG].as_str().unwrap();
+        assert_eq!(key.decrypt(ciphertext).unwrap(), "k-123");
+        assert_eq!(document["variables"]["note"], "!secret is just text");
+
+        let changed = rewrite_environment_variables(file.path(), |_, value| match value {
+            VariableValue::Secret(ciphertext) => Ok(VariableValue::Plain(key.decrypt(&ciphertext)?)),
+            other => Ok(other),
+        }).unwrap();
+        assert_eq!(changed, 1);
+        let document: serde_json::Value = serde_json::from_str(&std::fs::read_to_string(file.path()).unwrap()).unwrap();
+        assert_eq!(document["variables"]["api_key"], "k-123");
+    }
+
+    #[test]
+    fn test_rewrite_json_keeps_key_order() {
+        let mut file = TempFileBuilder::new().suffix(".json").tempfile().unwrap();
+        write!(file, r#"{{"variables": {{"zone": "eu", "api_key": "k-123"}}, "name": "Dev"}}"#).unwrap();
+        file.flush().unwrap();
+
+        let changed = rewrite_environment_variables(file.path(), |name, value| match value {
+            VariableValue::Plain(_) if name == "api_key" => Ok(VariableValue::Plain("k-456".to_string())),
+            other => Ok(other),
+        }).unwrap();
+        assert_eq!(changed, 1);
+
+        let content = std::fs::read_to_string(file.path()).unwrap();
+        let positions: Vec<_> = ["\"variables\"", "\"zone\"", "\"api_key\": \"k-456\"", "\"name\""].iter()
+            .map(|needle| content.find(needle).unwrap())
+            .collect();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]), "keys were reordered:\n{}", content);
+    }
+
+    #[test]
+    fn test_rewrite_yaml_flow_variables_is_an_error() {
+        let mut file = TempFileBuilder::new().suffix(".yaml").tempfile().unwrap();
+        write!(file, "name: Dev\nvariables: {{api_key: k-123}}\n").unwrap();
+        file.flush().unwrap();
+
+        let result = rewrite_environment_variables(file.path(), |_, value| Ok(value));
+        match result {
+            Err(ValidationError::Parse(message)) => assert!(message.contains("own line"), "{}", message),
+            other => panic!("Expected a parse error, got {:?}", other),
+        }
+        assert_eq!(std::fs::read_to_string(file.path()).unwrap(), "name: Dev\nvariables: {api_key: k-123}\n");
+    }
+}
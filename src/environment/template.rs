@@ -0,0 +1,267 @@
+use base64::Engine;
+use handlebars::{
+    handlebars_helper, Context, Handlebars, Helper, HelperResult, Output, RenderContext, RenderError,
+};
+use hmac::{Hmac, Mac};
+use regex::Regex;
+use sha2::{Digest, Sha256};
+
+use crate::request::ValidationError;
+use crate::utils::{hex_encode, percent_encode};
+
+use super::EnvironmentResolver;
+
+/// Renders request files that opt in with `template: handlebars`.
+///
+/// The whole file is rendered before it is parsed, so conditionals and loops
+/// can shape any part of the request. Plain `{{name}}` placeholders keep
+/// working; built-in `{{$uuid}}` and `{{$env.NAME}}` placeholders are filled
+/// in before Handlebars sees the text.
+///
+/// Values are escaped for a double-quoted YAML or JSON string, so write
+/// placeholders inside quotes (`url: "{{base_url}}/users"`); `{{{name}}}`
+/// inserts a value as is. Without strict mode, a placeholder that has no
+/// value is written back unchanged.
+///
+/// Helpers: `base64`, `urlencode`, `json` (raw JSON, for unquoted use),
+/// `upper`, `sha256`, `hmac key message` (HMAC-SHA256, hex) and
+/// `default value fallback`.
+pub struct TemplateRenderer;
+
+impl TemplateRenderer {
+    /// True when `content` opts in to Handlebars rendering.
+    pub fn is_handlebars(content: &str) -> bool {
+        Regex::new(r#"(?m)^\s*"?template"?\s*:\s*["']?handlebars["']?\s*,?\s*$"#)
+            .unwrap() // Infallible regex
+            .is_match(content)
+    }
+
+    pub fn render(content: &str, env_resolver: &EnvironmentResolver) -> Result<String, ValidationError> {
+        let content = Self::resolve_builtins(content, env_resolver)?;
+
+        let mut handlebars = Handlebars::new();
+        handlebars.register_escape_fn(escape_quoted);
+        handlebars.set_strict_mode(env_resolver.is_strict());
+        register_helpers(&mut handlebars);
+        if !env_resolver.is_strict() {
+            handlebars.register_helper("helperMissing", Box::new(keep_placeholder));
+        }
+
+        handlebars
+            .render_template(&content, &env_resolver.template_context()?)
+            .map_err(|e| ValidationError::Template(e.to_string()))
+    }
+
+    /// Replaces `{{$...}}` placeholders, which are not valid Handlebars
+    /// expressions. Unknown ones are an error in strict mode and are otherwise
+    /// escaped so they come out of rendering unchanged.
+    fn resolve_builtins(content: &str, env_resolver: &EnvironmentResolver) -> Result<String, ValidationError> {
+        let mut unresolved = Vec::new();
+        let mut error = None;
+        let builtin = Regex::new(r"\{\{\s*(\$[^{}]+?)\s*\}\}").unwrap(); // Infallible regex
+
+        let resolved = builtin.replace_all(content, |caps: &regex::Captures| {
+            let placeholder = format!("{{{{{}}}}}", &caps[1]);
+            match env_resolver.resolve_template_checked(&placeholder) {
+                Ok((value, missing)) if missing.is_empty() => value,
+                Ok(_) => {
+                    unresolved.push(placeholder);
+                    format!("\\{}", &caps[0])
+                }
+                Err(e) => {
+                    error.get_or_insert(e);
+                    caps[0].to_string()
+                }
+            }
+        });
+
+        if let Some(error) = error {
+            return Err(error);
+        }
+
+        if env_resolver.is_strict() && !unresolved.is_empty() {
+            unresolved.sort();
+            unresolved.dedup();
+            return Err(ValidationError::UnresolvedVariables(unresolved));
+        }
+
+        Ok(resolved.into_owned())
+    }
+}
+
+handlebars_helper!(base64_helper: |value: str| base64::engine::general_purpose::STANDARD.encode(value));
+handlebars_helper!(urlencode_helper: |value: str| percent_encode(value));
+handlebars_helper!(upper_helper: |value: str| value.to_uppercase());
+handlebars_helper!(sha256_helper: |value: str| hex_encode(&Sha256::digest(value.as_bytes())));
+handlebars_helper!(hmac_helper: |key: str, message: str| {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes()).expect("HMAC accepts keys of any length");
+    mac.update(message.as_bytes());
+    hex_encode(&mac.finalize().into_bytes())
+});
+
+/// Escapes `value` for the inside of a double-quoted YAML or JSON string.
+fn escape_quoted(value: &str) -> String {
+    let quoted = serde_json::to_string(value).unwrap_or_default();
+    quoted[1..quoted.len() - 1].to_string()
+}
+
+/// `{{json value}}`: the value as JSON, written unescaped so it can stand
+/// as a YAML flow value.
+fn json_helper(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let value = h.param(0).map(|param| param.value().clone()).unwrap_or_default();
+    out.write(&serde_json::to_string(&value).unwrap_or_default())?;
+    Ok(())
+}
+
+/// Lenient mode's `helperMissing` hook: a `{{name}}` with no value is
+/// written back as it was, like unresolved placeholders outside templates.
+fn keep_placeholder(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    if !h.params().is_empty() || !h.hash().is_empty() {
+        return Err(RenderError::new(format!("Helper not defined: {:?}", h.name())));
+    }
+    out.write(&format!("{{{{{}}}}}", h.name()))?;
+    Ok(())
+}
+
+/// `{{default value "fallback"}}`: the fallback when `value` is missing,
+/// null or empty. Never a strict-mode error, since that is its purpose.
+fn default_helper(
+    h: &Helper,
+    handlebars: &Handlebars,
+    _: &Context,
+    rc: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let is_set = |value: &serde_json::Value| !value.is_null() && value.as_str() != Some("");
+
+    let chosen = h.param(0)
+        .filter(|param| !param.is_value_missing())
+        .map(|param| param.value())
+        .filter(|value| is_set(value))
+        .or_else(|| h.param(1).map(|param| param.value()));
+
+    let text = match chosen {
+        Some(serde_json::Value::String(text)) => text.clone(),
+        Some(serde_json::Value::Null) | None => return Ok(()),
+        Some(value) => value.to_string(),
+    };
+    if rc.is_disable_escape() {
+        out.write(&text)?;
+    } else {
+        out.write(&handlebars.get_escape_fn()(&text))?;
+    }
+    Ok(())
+}
+
+fn register_helpers(handlebars: &mut Handlebars) {
+    handlebars.register_helper("base64", Box::new(base64_helper));
+    handlebars.register_helper("urlencode", Box::new(urlencode_helper));
+    handlebars.register_helper("json", Box::new(json_helper));
+    handlebars.register_helper("upper", Box::new(upper_helper));
+    handlebars.register_helper("sha256", Box::new(sha256_helper));
+    handlebars.register_helper("hmac", Box::new(hmac_helper));
+    handlebars.register_helper("default", Box::new(default_helper));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolver() -> EnvironmentResolver {
+        let mut resolver = EnvironmentResolver::default();
+        resolver.set_variable("user", "john doe");
+        resolver.set_variable("ids", "[1, 2, 3]");
+        resolver.set_variable("admin", "false");
+        resolver
+    }
+
+    #[test]
+    fn test_is_handlebars() {
+        assert!(TemplateRenderer::is_handlebars("name: A\ntemplate: handlebars\n"));
+        assert!(TemplateRenderer::is_handlebars("{\n  \"template\": \"handlebars\",\n  \"name\": \"A\"\n}"));
+        assert!(!TemplateRenderer::is_handlebars("name: A\nurl: http://x/template: handlebars"));
+    }
+
+    #[test]
+    fn test_render_loops_conditionals_and_plain_placeholders() {
+        let template = "user: {{user}}\nids: [{{#each ids}}{{this}}{{#unless @last}}, {{/unless}}{{/each}}]\n{{#if admin}}role: admin{{else}}role: user{{/if}}";
+        let rendered = TemplateRenderer::render(template, &resolver()).unwrap();
+        assert_eq!(rendered, "user: john doe\nids: [1, 2, 3]\nrole: user");
+    }
+
+    #[test]
+    fn test_render_helpers() {
+        let template = "{{base64 user}}|{{urlencode user}}|{{json ids}}|{{upper user}}|{{sha256 \"abc\"}}|{{hmac \"key\" \"The quick brown fox jumps over the lazy dog\"}}|{{default missing \"none\"}}|{{default user \"none\"}}";
+        let rendered = TemplateRenderer::render(template, &resolver()).unwrap();
+        let parts: Vec<_> = rendered.split('|').collect();
+
+        assert_eq!(parts[0], "am9obiBkb2U=");
+        assert_eq!(parts[1], "john%20doe");
+        assert_eq!(parts[2], "[1,2,3]");
+        assert_eq!(parts[3], "JOHN DOE");
+        assert_eq!(parts[4], "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(parts[5], "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8");
+        assert_eq!(parts[6], "none");
+        assert_eq!(parts[7], "john doe");
+    }
+
+    #[test]
+    fn test_render_builtins_and_strict_mode() {
+        let mut resolver = resolver();
+        let rendered = TemplateRenderer::render("{{$uuid}} {{$unknown}}", &resolver).unwrap();
+        assert_eq!(rendered.len(), 36 + " {{$unknown}}".len());
+        assert!(rendered.ends_with(" {{$unknown}}"));
+
+        resolver.set_strict(true);
+        assert!(TemplateRenderer::render("{{default missing \"x\"}}", &resolver).is_ok());
+        assert!(matches!(
+            TemplateRenderer::render("{{missing}}", &resolver),
+            Err(ValidationError::Template(_))
+        ));
+        assert!(matches!(
+            TemplateRenderer::render("{{missing.name}}", &resolver),
+            Err(ValidationError::Template(_))
+        ));
+        match TemplateRenderer::render("{{$unknown}}", &resolver) {
+            Err(ValidationError::UnresolvedVariables(names)) => assert_eq!(names, vec!["{{$unknown}}".to_string()]),
+            other => panic!("Expected unresolved variables, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_render_lenient_keeps_unknown_placeholders() {
+        let rendered = TemplateRenderer::render(
+            "url: \"{{base_url}}/users/{{user}}/{{missing.id}}\"\nroles:\n{{#each roles}}  - {{this}}\n{{/each}}",
+            &resolver(),
+        ).unwrap();
+        assert_eq!(rendered, "url: \"{{base_url}}/users/john doe/{{missing.id}}\"\nroles:\n");
+    }
+
+    #[test]
+    fn test_render_escapes_values_for_quoted_strings() {
+        let mut resolver = resolver();
+        resolver.set_variable("note", "say \"hi\"\nthen \\leave");
+        let rendered = TemplateRenderer::render(
+            "note: \"{{note}}\"\nfallback: \"{{default missing note}}\"\nraw: '{{{user}}}'\nids: {{json ids}}",
+            &resolver,
+        ).unwrap();
+
+        let parsed: serde_json::Value = serde_yaml::from_str(&rendered).unwrap();
+        assert_eq!(parsed["note"], "say \"hi\"\nthen \\leave");
+        assert_eq!(parsed["fallback"], "say \"hi\"\nthen \\leave");
+        assert_eq!(parsed["raw"], "john doe");
+        assert_eq!(parsed["ids"], serde_json::json!([1, 2, 3]));
+    }
+}
@@ -1,20 +1,256 @@
 use serde::{Deserialize, Serialize};
-use std::{collections::HashMap, path::Path};
+use std::{collections::HashMap, path::{Path, PathBuf}};
 use regex::Regex;
 
 use crate::utils::load_and_parse_file;
 use crate::request::ValidationError;
 
+use super::{is_dotenv_file, DynamicVariables, SecretKey, VariableValue, SECRET_PREFIX, SECRET_TAG};
+
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Environment {
     pub name: String,
+    /// Parent environment file, relative to this file. Its variables are
+    /// inherited and overridden by this environment's own.
+    pub extends: Option<String>,
+    /// Numbers and booleans are read as their text, lists and maps as JSON,
+    /// and `!secret` values as `SECRET_PREFIX` and the ciphertext until
+    /// decrypted.
+    #[serde(default, deserialize_with = "deserialize_variables")]
     pub variables: Option<HashMap<String, String>>,
 }
 
-#[derive(Default, Debug)]
+impl Environment {
+    /// Loads the environment at `path` like `load_raw`, then decrypts its
+    /// `!secret` values with the key from `SecretKey::from_env`. The key is
+    /// only needed when there are secrets.
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Environment, ValidationError> {
+        let mut environment = Self::load_raw(path)?;
+        if environment.has_secrets() {
+            environment.decrypt_secrets(&SecretKey::from_env()?)?;
+        }
+        Ok(environment)
+    }
+
+    /// Loads the environment at `path` and merges in every environment it
+    /// `extends`, parents first. A chain that loops back on itself is an error.
+    /// Dotenv files (see `is_dotenv_file`) are read too. Secrets are left
+    /// encrypted.
+    pub fn load_raw<P: AsRef<Path>>(path: P) -> Result<Environment, ValidationError> {
+        let mut chain: Vec<(PathBuf, Environment)> = Vec::new();
+        let mut next = Some(path.as_ref().to_path_buf());
+
+        while let Some(path) = next {
+            let canonical = path.canonicalize()
+                .map_err(|e| ValidationError::FileIo(format!("Failed to read file {}: {}", path.display(), e)))?;
+
+            if chain.iter().any(|(seen, _)| *seen == canonical) {
+                let mut names: Vec<_> = chain.iter().map(|(seen, _)| seen.display().to_string()).collect();
+                names.push(canonical.display().to_string());
+                return Err(ValidationError::EnvironmentCycle(names.join(" -> ")));
+            }
+
+            let environment: Environment = if is_dotenv_file(&canonical) {
+                Environment::from_dotenv(&canonical)?
+            } else {
+                load_and_parse_file(&canonical)?
+            };
+            next = environment.extends.as_ref().map(|parent| {
+                canonical.parent().unwrap_or_else(|| Path::new(".")).join(parent)
+            });
+            chain.push((canonical, environment));
+        }
+
+        let mut variables = HashMap::new();
+        for (_, environment) in chain.iter().rev() {
+            if let Some(own) = &environment.variables {
+                variables.extend(own.clone());
+            }
+        }
+
+        let (_, mut environment) = chain.swap_remove(0);
+        environment.variables = Some(variables);
+        Ok(environment)
+    }
+
+    pub fn has_secrets(&self) -> bool {
+        self.variables.iter().flatten()
+            .any(|(_, value)| matches!(VariableValue::parse(value), VariableValue::Secret(_)))
+    }
+
+    pub fn decrypt_secrets(&mut self, key: &SecretKey) -> Result<(), ValidationError> {
+        for (name, value) in self.variables.iter_mut().flatten() {
+            if let VariableValue::Secret(ciphertext) = VariableValue::parse(value) {
+                *value = key.decrypt(&ciphertext)
+                    .map_err(|e| ValidationError::Secret(format!("Variable '{}': {}", name, e)))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// How a variable differs between two environments.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableDiff {
+    OnlyLeft(String),
+    OnlyRight(String),
+    Changed(String),
+    /// Encrypted on at least one side and no key to compare with.
+    Encrypted(String),
+}
+
+impl Environment {
+    /// Variables for display, sorted by name, with `!secret` values and
+    /// values of sensitive-looking names (tokens, passwords, keys) masked.
+    pub fn masked_variables(&self) -> Vec<(String, String)> {
+        let mut variables: Vec<_> = self.variables.iter().flatten()
+            .map(|(name, value)| (name.clone(), mask_value(name, value)))
+            .collect();
+        variables.sort();
+        variables
+    }
+
+    /// The variables that are missing on one side or differ, sorted by name.
+    /// Secrets are compared by their decrypted values, so fresh nonces don't
+    /// count as a change; without a `key` they are reported as `Encrypted`.
+    pub fn diff(&self, other: &Environment, key: Option<&SecretKey>) -> Result<Vec<VariableDiff>, ValidationError> {
+        let empty = HashMap::new();
+        let left = self.variables.as_ref().unwrap_or(&empty);
+        let right = other.variables.as_ref().unwrap_or(&empty);
+
+        let mut names: Vec<_> = left.keys().chain(right.keys()).collect();
+        names.sort();
+        names.dedup();
+
+        let plaintext = |name: &str, value: &str| match (VariableValue::parse(value), key) {
+            (VariableValue::Plain(text), _) => Ok(Some(text)),
+            (VariableValue::Secret(ciphertext), Some(key)) => key.decrypt(&ciphertext)
+                .map(Some)
+                .map_err(|e| ValidationError::Secret(format!("Variable '{}': {}", name, e))),
+            (VariableValue::Secret(_), None) => Ok(None),
+        };
+
+        let mut differences = Vec::new();
+        for name in names {
+            let difference = match (left.get(name), right.get(name)) {
+                (Some(_), None) => Some(VariableDiff::OnlyLeft(name.clone())),
+                (None, Some(_)) => Some(VariableDiff::OnlyRight(name.clone())),
+                (Some(a), Some(b)) if a == b => None,
+                (Some(a), Some(b)) => match (plaintext(name, a)?, plaintext(name, b)?) {
+                    (Some(a), Some(b)) if a == b => None,
+                    (Some(_), Some(_)) => Some(VariableDiff::Changed(name.clone())),
+                    _ => Some(VariableDiff::Encrypted(name.clone())),
+                },
+                (None, None) => None,
+            };
+            differences.extend(difference);
+        }
+        Ok(differences)
+    }
+}
+
+/// Hides `value` when it is encrypted or its name suggests a credential.
+pub fn mask_value(name: &str, value: &str) -> String {
+    if value.starts_with(SECRET_PREFIX) {
+        return "******** (encrypted)".to_string();
+    }
+    let lowercase = name.to_lowercase();
+    let sensitive = [
+        "password", "secret", "token", "api_key", "api-key", "apikey", "private_key", "credential",
+        "authorization", "cookie", "signature",
+    ]
+        .iter()
+        .any(|marker| lowercase.contains(marker));
+    if sensitive && !value.is_empty() {
+        "********".to_string()
+    } else {
+        value.to_string()
+    }
+}
+
+fn deserialize_variables<'de, D>(deserializer: D) -> Result<Option<HashMap<String, String>>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+{
+    let raw: Option<HashMap<String, serde_yaml::Value>> = Option::deserialize(deserializer)?;
+    Ok(raw.map(|variables| {
+        variables.into_iter()
+            .map(|(name, value)| (name, variable_text(value)))
+            .collect()
+    }))
+}
+
+fn variable_text(value: serde_yaml::Value) -> String {
+    match value {
+        serde_yaml::Value::String(text) => text,
+        serde_yaml::Value::Null => String::new(),
+        serde_yaml::Value::Bool(flag) => flag.to_string(),
+        serde_yaml::Value::Number(number) => number.to_string(),
+        serde_yaml::Value::Tagged(tagged) if tagged.tag == "secret" => {
+            VariableValue::Secret(variable_text(tagged.value)).to_raw()
+        }
+        serde_yaml::Value::Mapping(mapping) if mapping.len() == 1 && mapping.contains_key(SECRET_TAG) => {
+            let ciphertext = mapping.get(SECRET_TAG).cloned().unwrap_or_default();
+            VariableValue::Secret(variable_text(ciphertext)).to_raw()
+        }
+        serde_yaml::Value::Tagged(tagged) => variable_text(tagged.value),
+        other => serde_json::to_string(&other).unwrap_or_default(),
+    }
+}
+
+/// Where a resolved variable came from.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VariableSource {
+    /// Set by the workflow step being run.
+    Step,
+    /// Captured from an earlier response during the run.
+    Runtime,
+    /// Given with `--var key=value`.
+    CommandLine,
+    /// Defined in the named environment file.
+    Environment(String),
+    /// Read from the process environment through `{{$env.NAME}}`.
+    Os,
+    /// Generated by a built-in variable such as `{{$uuid}}`.
+    Dynamic,
+}
+
+impl std::fmt::Display for VariableSource {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            VariableSource::Step => write!(f, "workflow step"),
+            VariableSource::Runtime => write!(f, "runtime"),
+            VariableSource::CommandLine => write!(f, "command line"),
+            VariableSource::Environment(name) => write!(f, "environment '{}'", name),
+            VariableSource::Os => write!(f, "OS environment"),
+            VariableSource::Dynamic => write!(f, "built-in generator"),
+        }
+    }
+}
+
+/// Resolves `{{name}}`, `{{$env.NAME}}` and built-in `{{$uuid}}` style
+/// placeholders (see `DynamicVariables`). Named placeholders accept a
+/// default, `{{name:-fallback}}`, and values may reference other variables.
+///
+/// A `{{name}}` is looked up in this order, first match wins:
+/// 1. the `variables` of the workflow step being run
+/// 2. command-line variables (`--var`)
+/// 3. runtime variables: values captured from earlier responses, which sit
+///    above the files the run started with
+/// 4. environment file variables
+///
+/// A `{{$env.NAME}}` is looked up in command-line variables, then
+/// environment file variables, then the OS environment. Step variables and
+/// captured values never shadow it.
+#[derive(Default, Debug, Clone)]
 pub struct EnvironmentResolver {
     active_variables: Option<HashMap<String, String>>,
     active_environment_name: Option<String>, 
+    cli_variables: HashMap<String, String>,
+    runtime_variables: HashMap<String, String>,
+    step_variables: HashMap<String, String>,
+    dynamic: DynamicVariables,
+    strict: bool,
 }
 
 impl EnvironmentResolver {
@@ -22,7 +258,7 @@ impl EnvironmentResolver {
         &mut self,
         path: P,
     ) -> Result<(), ValidationError> {
-        let loaded_environment: Environment = load_and_parse_file(path)?;
+        let loaded_environment = Environment::load(path)?;
         
         self.active_environment_name = Some(loaded_environment.name);
         self.active_variables = loaded_environment.variables;
@@ -30,26 +266,239 @@ impl EnvironmentResolver {
         Ok(())
     }
 
+    /// Loads an environment on top of the current one: its variables override
+    /// existing ones with the same name and it becomes the active environment.
+    pub fn layer_environment_file<P: AsRef<Path>>(
+        &mut self,
+        path: P,
+    ) -> Result<(), ValidationError> {
+        let loaded_environment = Environment::load(path)?;
+
+        self.active_environment_name = Some(loaded_environment.name);
+        self.active_variables
+            .get_or_insert_with(HashMap::new)
+            .extend(loaded_environment.variables.unwrap_or_default());
+
+        Ok(())
+    }
+
     pub fn resolve_template(&self, template: &str) -> String { 
-        if let Some(vars) = &self.active_variables {
-            let re = Regex::new(r"\{\{([a-zA-Z0-9_]+)\}\}").unwrap(); // Infallible regex
-            
-            let resolved_string = re.replace_all(template, |caps: &regex::Captures| {
-                let var_name = &caps[1];
-                vars.get(var_name) 
-                    .cloned()
-                    .unwrap_or(caps[0].to_string()) 
-            });
-            return resolved_string.into_owned(); 
+        self.resolve_template_checked(template)
+            .map(|(resolved, _)| resolved)
+            .unwrap_or_else(|_| template.to_string())
+    }
+
+    /// Resolves `template` and also returns every placeholder that had no
+    /// value. Unresolved placeholders are left in the output as they were.
+    ///
+    /// `{{name:-fallback}}` uses `fallback` when `name` is unset or empty.
+    /// Values that contain placeholders themselves are resolved recursively,
+    /// up to `MAX_NESTING_DEPTH` levels; a variable that refers back to
+    /// itself is an error naming the chain.
+    pub fn resolve_template_checked(&self, template: &str) -> Result<(String, Vec<String>), ValidationError> {
+        let mut unresolved = Vec::new();
+        let resolved = self.expand(template, &mut Vec::new(), &mut unresolved)?;
+        Ok((resolved, unresolved))
+    }
+
+    fn expand(
+        &self,
+        template: &str,
+        chain: &mut Vec<String>,
+        unresolved: &mut Vec<String>,
+    ) -> Result<String, ValidationError> {
+        let mut error = None;
+
+        let resolved_string = placeholder_regex().replace_all(template, |caps: &regex::Captures| {
+            if error.is_some() {
+                return caps[0].to_string();
+            }
+
+            let (placeholder, fallback) = split_fallback(&caps[1]);
+            match self.expand_variable(placeholder, chain, unresolved) {
+                Ok(Some(value)) if !(value.is_empty() && fallback.is_some()) => value,
+                Ok(_) => match fallback {
+                    Some(fallback) => fallback.to_string(),
+                    None => {
+                        unresolved.push(caps[0].to_string());
+                        caps[0].to_string()
+                    }
+                },
+                Err(e) => {
+                    error = Some(e);
+                    caps[0].to_string()
+                }
+            }
+        });
+
+        match error {
+            Some(error) => Err(error),
+            None => Ok(resolved_string.into_owned()),
         }
+    }
 
-        // If no active variables, return the template as is.
-        template.to_string()
+    /// Looks up `placeholder` and resolves any placeholders inside its value.
+    fn expand_variable(
+        &self,
+        placeholder: &str,
+        chain: &mut Vec<String>,
+        unresolved: &mut Vec<String>,
+    ) -> Result<Option<String>, ValidationError> {
+        let (value, source) = match self.lookup(placeholder) {
+            Some(found) => found,
+            None => return Ok(None),
+        };
+        if source == VariableSource::Dynamic || !value.contains("{{") {
+            return Ok(Some(value));
+        }
+
+        let name = placeholder.strip_prefix("$env.").unwrap_or(placeholder);
+        if chain.iter().any(|seen| seen == name) {
+            chain.push(name.to_string());
+            return Err(ValidationError::VariableCycle(chain.join(" -> ")));
+        }
+        if chain.len() >= MAX_NESTING_DEPTH {
+            chain.push(name.to_string());
+            return Err(ValidationError::VariableNesting(format!(
+                "more than {} levels: {}", MAX_NESTING_DEPTH, chain.join(" -> ")
+            )));
+        }
+
+        chain.push(name.to_string());
+        let expanded = self.expand(&value, chain, unresolved);
+        chain.pop();
+        expanded.map(Some)
+    }
+
+    /// Looks up the value of a placeholder (the text between the braces)
+    /// and reports which source it came from.
+    pub fn lookup(&self, placeholder: &str) -> Option<(String, VariableSource)> {
+        if placeholder.starts_with('$') && !placeholder.starts_with("$env.") {
+            return self.dynamic.generate(placeholder).map(|value| (value, VariableSource::Dynamic));
+        }
+
+        if let Some(name) = placeholder.strip_prefix("$env.") {
+            if let Some(value) = self.cli_variables.get(name) {
+                return Some((value.clone(), VariableSource::CommandLine));
+            }
+            if let Some(value) = self.active_variables.as_ref().and_then(|vars| vars.get(name)) {
+                let environment = self.active_environment_name.clone().unwrap_or_default();
+                return Some((value.clone(), VariableSource::Environment(environment)));
+            }
+            return std::env::var(name).ok().map(|value| (value, VariableSource::Os));
+        }
+
+        if let Some(value) = self.step_variables.get(placeholder) {
+            return Some((value.clone(), VariableSource::Step));
+        }
+        if let Some(value) = self.cli_variables.get(placeholder) {
+            return Some((value.clone(), VariableSource::CommandLine));
+        }
+        if let Some(value) = self.runtime_variables.get(placeholder) {
+            return Some((value.clone(), VariableSource::Runtime));
+        }
+        if let Some(value) = self.active_variables.as_ref().and_then(|vars| vars.get(placeholder)) {
+            let environment = self.active_environment_name.clone().unwrap_or_default();
+            return Some((value.clone(), VariableSource::Environment(environment)));
+        }
+
+        None
+    }
+
+    /// Builds the data a Handlebars template is rendered against: every named
+    /// variable, with the same precedence as `lookup`. Values holding a JSON
+    /// array, object or boolean are passed as structured data so templates
+    /// can loop over and branch on them. Nested placeholders in values are
+    /// resolved first.
+    pub fn template_context(&self) -> Result<serde_json::Value, ValidationError> {
+        let mut merged: HashMap<&String, &String> = HashMap::new();
+        for layer in [self.active_variables.as_ref(), Some(&self.runtime_variables), Some(&self.cli_variables), Some(&self.step_variables)] {
+            merged.extend(layer.into_iter().flatten());
+        }
+
+        let mut context = serde_json::Map::new();
+        for (name, value) in merged {
+            let value = &self.resolve_template_checked(value)?.0;
+            let structured = match value.trim_start().chars().next() {
+                Some('[') | Some('{') | Some('t') | Some('f') => serde_json::from_str::<serde_json::Value>(value)
+                    .ok()
+                    .filter(|parsed| parsed.is_array() || parsed.is_object() || parsed.is_boolean()),
+                _ => None,
+            };
+            context.insert(name.clone(), structured.unwrap_or_else(|| serde_json::Value::String(value.clone())));
+        }
+
+        Ok(serde_json::Value::Object(context))
+    }
+
+    /// Lists each distinct placeholder in `template` that resolves, with the
+    /// source of its value.
+    pub fn variable_sources(&self, template: &str) -> Vec<(String, VariableSource)> {
+        let mut sources: Vec<(String, VariableSource)> = Vec::new();
+        for caps in placeholder_regex().captures_iter(template) {
+            let placeholder = split_fallback(&caps[1]).0.to_string();
+            if sources.iter().any(|(seen, _)| *seen == placeholder) {
+                continue;
+            }
+            if DynamicVariables::recognizes(&placeholder) {
+                // Don't generate a value here; that would advance a seeded sequence.
+                sources.push((placeholder, VariableSource::Dynamic));
+            } else if let Some((_, source)) = self.lookup(&placeholder) {
+                sources.push((placeholder, source));
+            }
+        }
+        sources
+    }
+
+    /// Replaces the generator behind the built-in variables, e.g. with a seeded one.
+    pub fn set_dynamic_variables(&mut self, dynamic: DynamicVariables) {
+        self.dynamic = dynamic;
+    }
+
+    /// Sets a variable given on the command line; it overrides every other source.
+    pub fn set_cli_variable<K: Into<String>, V: Into<String>>(&mut self, name: K, value: V) {
+        self.cli_variables.insert(name.into(), value.into());
+    }
+
+    /// In strict mode, resolving a request fails when any placeholder is left unresolved.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
     }
 
     pub fn active_environment_name(&self) -> Option<&str> {
         self.active_environment_name.as_deref()
     }
+
+    /// Sets a variable for the rest of the run, e.g. a value captured from a
+    /// response. It overrides environment files but not `--var`.
+    pub fn set_variable<K: Into<String>, V: Into<String>>(&mut self, name: K, value: V) {
+        self.runtime_variables.insert(name.into(), value.into());
+    }
+
+    /// Sets a variable from a workflow step's `variables`; it overrides every
+    /// other source, `--var` included. Set it on a per-step clone.
+    pub fn set_step_variable<K: Into<String>, V: Into<String>>(&mut self, name: K, value: V) {
+        self.step_variables.insert(name.into(), value.into());
+    }
+}
+
+/// How deep variables may refer to other variables before resolution gives up.
+pub const MAX_NESTING_DEPTH: usize = 10;
+
+fn placeholder_regex() -> Regex {
+    Regex::new(r"\{\{((?:\$env\.[a-zA-Z0-9_]+|[a-zA-Z0-9_]+)(?::-[^{}]*)?|\$[a-zA-Z]+(?: [+-]\d+[a-z])?)\}\}").unwrap() // Infallible regex
+}
+
+/// Splits `name:-fallback` into the name and the fallback, if any.
+fn split_fallback(placeholder: &str) -> (&str, Option<&str>) {
+    match placeholder.split_once(":-") {
+        Some((name, fallback)) => (name, Some(fallback)),
+        None => (placeholder, None),
+    }
 }
 
 #[cfg(test)]
@@ -61,7 +510,7 @@ mod tests {
     #[test]
     fn test_load_environment_file_sets_active() {
         let yaml_content = "name: TestEnvActive\nvariables:\n  key1: val1\n  api_key: secret";
-        let mut temp_file = TempFileBuilder::new().suffix(".yaml").NamedTempFile().unwrap();
+        let mut temp_file = TempFileBuilder::new().suffix(".yaml").tempfile().unwrap();
         write!(temp_file, "{}", yaml_content).unwrap();
         temp_file.flush().unwrap();
 
@@ -82,7 +531,7 @@ mod tests {
         
         // Load first environment
         let yaml_content1 = "name: Env1\nvariables:\n  url: site1.com";
-        let mut temp_file1 = TempFileBuilder::new().suffix(".yaml").NamedTempFile().unwrap();
+        let mut temp_file1 = TempFileBuilder::new().suffix(".yaml").tempfile().unwrap();
         write!(temp_file1, "{}", yaml_content1).unwrap();
         temp_file1.flush().unwrap();
         resolver.load_environment_file(temp_file1.path()).unwrap();
@@ -92,7 +541,7 @@ mod tests {
 
         // Load second environment
         let yaml_content2 = "name: Env2\nvariables:\n  url: site2.com\n  token: tok123";
-        let mut temp_file2 = TempFileBuilder::new().suffix(".yaml").NamedTempFile().unwrap();
+        let mut temp_file2 = TempFileBuilder::new().suffix(".yaml").tempfile().unwrap();
         write!(temp_file2, "{}", yaml_content2).unwrap();
         temp_file2.flush().unwrap();
         resolver.load_environment_file(temp_file2.path()).unwrap();
@@ -106,14 +555,16 @@ mod tests {
 
     #[test]
     fn test_resolve_template_with_active_variables() {
-        let mut resolver = EnvironmentResolver::default();
-        resolver.active_variables = Some({
-            let mut vars = HashMap::new();
-            vars.insert("base_url".to_string(), "http://api.example.com".to_string());
-            vars.insert("user_id".to_string(), "123".to_string());
-            vars.insert("API_VERSION".to_string(), "v2".to_string());
-            vars
-        });
+        let resolver = EnvironmentResolver {
+            active_variables: Some({
+                let mut vars = HashMap::new();
+                vars.insert("base_url".to_string(), "http://api.example.com".to_string());
+                vars.insert("user_id".to_string(), "123".to_string());
+                vars.insert("API_VERSION".to_string(), "v2".to_string());
+                vars
+            }),
+            ..Default::default()
+        };
 
         let template1 = "{{base_url}}/users/{{user_id}}?version={{API_VERSION}}";
         assert_eq!(resolver.resolve_template(template1), "http://api.example.com/users/123?version=v2");
@@ -137,23 +588,329 @@ mod tests {
 
     #[test]
     fn test_resolve_template_empty_variables_map() {
-        let mut resolver = EnvironmentResolver::default();
-        resolver.active_variables = Some(HashMap::new()); // Empty map
+        let resolver = EnvironmentResolver {
+            active_variables: Some(HashMap::new()), // Empty map
+            ..Default::default()
+        };
         let template = "{{base_url}}/items";
         assert_eq!(resolver.resolve_template(template), "{{base_url}}/items");
     }
 
+    #[test]
+    fn test_resolve_template_checked_reports_unresolved() {
+        let mut resolver = EnvironmentResolver::default();
+        let (resolved, unresolved) = resolver.resolve_template_checked("{{base_url}}/{{id}}").unwrap();
+        assert_eq!(resolved, "{{base_url}}/{{id}}");
+        assert_eq!(unresolved, vec!["{{base_url}}", "{{id}}"]);
+
+        resolver.set_variable("base_url", "http://api");
+        let (resolved, unresolved) = resolver.resolve_template_checked("{{base_url}}/{{id}}").unwrap();
+        assert_eq!(resolved, "http://api/{{id}}");
+        assert_eq!(unresolved, vec!["{{id}}"]);
+    }
+
+    #[test]
+    fn test_variable_precedence_and_sources() {
+        std::env::set_var("RUSTMAN_TEST_CI_TOKEN", "from-os");
+        std::env::set_var("RUSTMAN_TEST_SHADOWED", "from-os");
+
+        let mut resolver = EnvironmentResolver {
+            active_variables: Some(HashMap::from([
+                ("base_url".to_string(), "http://file".to_string()),
+                ("token".to_string(), "file-token".to_string()),
+                ("RUSTMAN_TEST_SHADOWED".to_string(), "from-file".to_string()),
+            ])),
+            active_environment_name: Some("Dev".to_string()),
+            ..Default::default()
+        };
+        resolver.set_cli_variable("token", "cli-token");
+
+        // `$env.` prefers the environment file over the OS environment.
+        assert_eq!(
+            resolver.resolve_template("{{base_url}} {{token}} {{$env.RUSTMAN_TEST_CI_TOKEN}} {{$env.RUSTMAN_TEST_SHADOWED}}"),
+            "http://file cli-token from-os from-file"
+        );
+        // Plain placeholders never fall back to the OS environment.
+        assert_eq!(resolver.resolve_template("{{RUSTMAN_TEST_CI_TOKEN}}"), "{{RUSTMAN_TEST_CI_TOKEN}}");
+
+        // Captures override the file but not `--var`.
+        resolver.set_variable("token", "captured");
+        resolver.set_variable("base_url", "http://captured");
+        assert_eq!(resolver.resolve_template("{{base_url}} {{token}}"), "http://captured cli-token");
+
+        resolver.set_variable("RUSTMAN_TEST_SHADOWED", "captured");
+        assert_eq!(resolver.resolve_template("{{$env.RUSTMAN_TEST_SHADOWED}}"), "from-file");
+        resolver.set_cli_variable("RUSTMAN_TEST_SHADOWED", "from-cli");
+        assert_eq!(resolver.resolve_template("{{$env.RUSTMAN_TEST_SHADOWED}}"), "from-cli");
+
+        let sources = resolver.variable_sources("{{base_url}}{{token}}{{$env.RUSTMAN_TEST_CI_TOKEN}}{{$env.token}}{{missing}}{{token}}");
+        assert_eq!(sources, vec![
+            ("base_url".to_string(), VariableSource::Runtime),
+            ("token".to_string(), VariableSource::CommandLine),
+            ("$env.RUSTMAN_TEST_CI_TOKEN".to_string(), VariableSource::Os),
+            ("$env.token".to_string(), VariableSource::CommandLine),
+        ]);
+
+        // A workflow step's own variables override even `--var`.
+        resolver.set_step_variable("token", "step-token");
+        assert_eq!(resolver.resolve_template("{{token}}"), "step-token");
+        assert_eq!(resolver.variable_sources("{{token}}"), vec![("token".to_string(), VariableSource::Step)]);
+    }
+
+    #[test]
+    fn test_dynamic_variables_are_fresh_per_placeholder() {
+        let mut resolver = EnvironmentResolver::default();
+        resolver.set_dynamic_variables(DynamicVariables::with_seed(7));
+
+        let resolved = resolver.resolve_template("{{$uuid}} {{$uuid}}");
+        let ids: Vec<_> = resolved.split(' ').collect();
+        assert_eq!(ids.len(), 2);
+        assert_ne!(ids[0], ids[1]);
+
+        let mut replay = EnvironmentResolver::default();
+        replay.set_dynamic_variables(DynamicVariables::with_seed(7));
+        assert_eq!(replay.resolve_template("{{$uuid}} {{$uuid}}"), resolved);
+
+        let (_, unresolved) = resolver.resolve_template_checked("{{$unknown}} {{$now +1d}}").unwrap();
+        assert_eq!(unresolved, vec!["{{$unknown}}"]);
+        assert_eq!(resolver.variable_sources("{{$now +1d}}"), vec![("$now +1d".to_string(), VariableSource::Dynamic)]);
+    }
+
+    #[test]
+    fn test_set_variable_without_environment() {
+        let mut resolver = EnvironmentResolver::default();
+        resolver.set_variable("auth_token", "abc");
+        assert_eq!(resolver.resolve_template("Bearer {{auth_token}}"), "Bearer abc");
+    }
+
+    fn write_env(dir: &Path, file_name: &str, content: &str) -> PathBuf {
+        let path = dir.join(file_name);
+        let mut file = std::fs::File::create(&path).unwrap();
+        write!(file, "{}", content).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_environment_extends_chain() {
+        let temp_dir = TempFileBuilder::new().prefix("test_env_chain_").tempdir().unwrap();
+        let dir = temp_dir.path();
+        write_env(dir, "base.yaml", "name: Base\nvariables:\n  timeout: \"30\"\n  role: user\n  base_url: http://base");
+        std::fs::create_dir(dir.join("teams")).unwrap();
+        write_env(dir, "teams/shared.yaml", "name: Shared\nextends: ../base.yaml\nvariables:\n  timeout: \"20\"");
+        let dev = write_env(dir, "dev.yaml", "name: Dev\nextends: teams/shared.yaml\nvariables:\n  base_url: http://dev");
+
+        let environment = Environment::load(&dev).unwrap();
+        assert_eq!(environment.name, "Dev");
+        let vars = environment.variables.unwrap();
+        assert_eq!(vars.get("base_url"), Some(&"http://dev".to_string()));
+        assert_eq!(vars.get("timeout"), Some(&"20".to_string()));
+        assert_eq!(vars.get("role"), Some(&"user".to_string()));
+    }
+
+    #[test]
+    fn test_environment_extends_cycle_is_an_error() {
+        let temp_dir = TempFileBuilder::new().prefix("test_env_cycle_").tempdir().unwrap();
+        let dir = temp_dir.path();
+        let a = write_env(dir, "a.yaml", "name: A\nextends: b.yaml");
+        write_env(dir, "b.yaml", "name: B\nextends: a.yaml");
+
+        match Environment::load(&a) {
+            Err(ValidationError::EnvironmentCycle(chain)) => {
+                assert!(chain.contains("a.yaml -> "));
+                assert!(chain.ends_with("a.yaml"));
+            }
+            other => panic!("Expected EnvironmentCycle error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_layer_environment_file_merges_in_order() {
+        let temp_dir = TempFileBuilder::new().prefix("test_env_layer_").tempdir().unwrap();
+        let dir = temp_dir.path();
+        let first = write_env(dir, "first.yaml", "name: First\nvariables:\n  url: site1.com\n  token: tok1");
+        let second = write_env(dir, "second.yaml", "name: Second\nvariables:\n  url: site2.com");
+
+        let mut resolver = EnvironmentResolver::default();
+        resolver.layer_environment_file(&first).unwrap();
+        resolver.layer_environment_file(&second).unwrap();
+
+        assert_eq!(resolver.active_environment_name(), Some("Second"));
+        assert_eq!(resolver.resolve_template("{{url}} {{token}}"), "site2.com tok1");
+    }
+
     #[test]
     fn test_active_environment_name_accessor() {
         let mut resolver = EnvironmentResolver::default();
         assert_eq!(resolver.active_environment_name(), None);
 
         let yaml_content = "name: MyNamedEnv\nvariables:\n  var: val";
-        let mut temp_file = TempFileBuilder::new().suffix(".yaml").NamedTempFile().unwrap();
+        let mut temp_file = TempFileBuilder::new().suffix(".yaml").tempfile().unwrap();
         write!(temp_file, "{}", yaml_content).unwrap();
         temp_file.flush().unwrap();
         resolver.load_environment_file(temp_file.path()).unwrap();
 
         assert_eq!(resolver.active_environment_name(), Some("MyNamedEnv"));
     }
+
+    #[test]
+    fn test_resolve_template_fallbacks() {
+        let mut resolver = EnvironmentResolver::default();
+        resolver.set_variable("region", "eu");
+        resolver.set_variable("empty", "");
+
+        let (resolved, unresolved) = resolver
+            .resolve_template_checked("{{region:-us}} {{zone:-a-1}} {{empty:-none}} {{$env.RUSTMAN_TEST_UNSET_VAR:-local}} {{missing}}")
+            .unwrap();
+        assert_eq!(resolved, "eu a-1 none local {{missing}}");
+        assert_eq!(unresolved, vec!["{{missing}}".to_string()]);
+    }
+
+    #[test]
+    fn test_resolve_template_nested_variables() {
+        let mut resolver = EnvironmentResolver::default();
+        resolver.set_cli_variable("base_url", "http://api.example.com");
+        resolver.set_variable("api_url", "{{base_url}}/{{version:-v2}}");
+        resolver.set_variable("users_url", "{{api_url}}/users");
+
+        assert_eq!(resolver.resolve_template("{{users_url}}"), "http://api.example.com/v2/users");
+    }
+
+    #[test]
+    fn test_resolve_template_reports_cycles_and_depth() {
+        let mut resolver = EnvironmentResolver::default();
+        resolver.set_variable("a", "{{b}}");
+        resolver.set_variable("b", "x{{c}}");
+        resolver.set_variable("c", "{{a}}");
+
+        match resolver.resolve_template_checked("{{a}}") {
+            Err(ValidationError::VariableCycle(chain)) => assert_eq!(chain, "a -> b -> c -> a"),
+            other => panic!("Expected cycle error, got {:?}", other),
+        }
+        assert_eq!(resolver.resolve_template("{{a}}"), "{{a}}");
+
+        let mut resolver = EnvironmentResolver::default();
+        for level in 0..=MAX_NESTING_DEPTH {
+            resolver.set_variable(format!("v{}", level), format!("{{{{v{}}}}}", level + 1));
+        }
+        assert!(matches!(
+            resolver.resolve_template_checked("{{v0}}"),
+            Err(ValidationError::VariableNesting(_))
+        ));
+    }
+
+    #[test]
+    fn test_environment_secrets_and_non_string_values() {
+        let (key, _) = SecretKey::generate();
+        let ciphertext = key.encrypt("hunter2").unwrap();
+        let yaml_content = format!(
+            "name: Secrets\nvariables:\n  password: !secret {}\n  retries: 3\n  verbose: true\n  roles: [admin, dev]\n  host: example.com",
+            ciphertext
+        );
+        let mut temp_file = TempFileBuilder::new().suffix(".yaml").tempfile().unwrap();
+        write!(temp_file, "{}", yaml_content).unwrap();
+        temp_file.flush().unwrap();
+
+        let mut environment = Environment::load_raw(temp_file.path()).unwrap();
+        assert!(environment.has_secrets());
+        let vars = environment.variables.as_ref().unwrap();
+        assert_eq!(VariableValue::parse(&vars["password"]), VariableValue::Secret(ciphertext.clone()));
+        assert_eq!(vars["retries"], "3");
+        assert_eq!(vars["verbose"], "true");
+        assert_eq!(vars["roles"], r#"["admin","dev"]"#);
+
+        environment.decrypt_secrets(&key).unwrap();
+        assert!(!environment.has_secrets());
+        assert_eq!(environment.variables.as_ref().unwrap()["password"], "hunter2");
+
+        let (wrong_key, _) = SecretKey::generate();
+        let mut environment = Environment::load_raw(temp_file.path()).unwrap();
+        match environment.decrypt_secrets(&wrong_key) {
+            Err(ValidationError::Secret(message)) => assert!(message.contains("'password'")),
+            other => panic!("Expected secret error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_secret_marker_needs_explicit_syntax() {
+        let temp_dir = TempFileBuilder::new().prefix("test_secret_marker_").tempdir().unwrap();
+        let files = [
+            ("dev.yaml", "name: dev\nvariables:\n  note: \"!secret not encrypted\"\n  token: !secret abc\n"),
+            ("dev.json", r#"{"name": "dev", "variables": {"note": "!secret not encrypted", "token": {"!secret": "abc"}}}"#),
+            ("dev.env", "NOTE='!secret not encrypted'\nTOKEN=!secret abc # rotate\n"),
+        ];
+
+        for (file_name, content) in files {
+            let path = temp_dir.path().join(file_name);
+            std::fs::write(&path, content).unwrap();
+            let environment = Environment::load_raw(&path).unwrap();
+            let vars = environment.variables.as_ref().unwrap();
+            let (note, token) = if file_name.ends_with(".env") { ("NOTE", "TOKEN") } else { ("note", "token") };
+
+            assert_eq!(VariableValue::parse(&vars[note]), VariableValue::Plain("!secret not encrypted".to_string()), "{}", file_name);
+            assert_eq!(VariableValue::parse(&vars[token]), VariableValue::Secret("abc".to_string()), "{}", file_name);
+            assert_eq!(mask_value("note", &vars[note]), "!secret not encrypted");
+        }
+    }
+
+    #[test]
+    fn test_layer_dotenv_file() {
+        let temp_dir = TempFileBuilder::new().prefix("test_dotenv_").tempdir().unwrap();
+        let dotenv_path = temp_dir.path().join(".env.staging");
+        std::fs::write(&dotenv_path, "HOST=staging.example.com\nBASE_URL=https://${HOST}\n").unwrap();
+
+        let mut resolver = EnvironmentResolver::default();
+        resolver.layer_environment_file(&dotenv_path).unwrap();
+
+        assert_eq!(resolver.active_environment_name(), Some("staging"));
+        assert_eq!(resolver.resolve_template("{{BASE_URL}}/users"), "https://staging.example.com/users");
+    }
+
+    #[test]
+    fn test_environment_diff_and_masking() {
+        let environment = |variables: &[(&str, &str)]| Environment {
+            name: "Env".to_string(),
+            extends: None,
+            variables: Some(variables.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()),
+        };
+        let dev = environment(&[("base_url", "http://dev"), ("auth_token", "t1"), ("debug", "true"), ("timeout", "30")]);
+        let prod = environment(&[("base_url", "http://prod"), ("auth_token", "\u{0}!secret abc"), ("timeout", "30"), ("replicas", "3")]);
+
+        assert_eq!(dev.diff(&prod, None).unwrap(), vec![
+            VariableDiff::Encrypted("auth_token".to_string()),
+            VariableDiff::Changed("base_url".to_string()),
+            VariableDiff::OnlyLeft("debug".to_string()),
+            VariableDiff::OnlyRight("replicas".to_string()),
+        ]);
+
+        assert_eq!(prod.masked_variables(), vec![
+            ("auth_token".to_string(), "******** (encrypted)".to_string()),
+            ("base_url".to_string(), "http://prod".to_string()),
+            ("replicas".to_string(), "3".to_string()),
+            ("timeout".to_string(), "30".to_string()),
+        ]);
+        assert_eq!(mask_value("user_password", "dev-password"), "********");
+    }
+
+    #[test]
+    fn test_environment_diff_decrypts_secrets() {
+        let (key, _) = SecretKey::generate();
+        let secret = |plaintext: &str| VariableValue::Secret(key.encrypt(plaintext).unwrap()).to_raw();
+        let environment = |variables: Vec<(&str, String)>| Environment {
+            name: "Env".to_string(),
+            extends: None,
+            variables: Some(variables.into_iter().map(|(k, v)| (k.to_string(), v)).collect()),
+        };
+        let dev = environment(vec![("token", secret("t1")), ("password", secret("p1")), ("api_key", "k1".to_string())]);
+        let prod = environment(vec![("token", secret("t1")), ("password", secret("p2")), ("api_key", secret("k1"))]);
+
+        assert_eq!(dev.diff(&prod, Some(&key)).unwrap(), vec![VariableDiff::Changed("password".to_string())]);
+        assert_eq!(dev.diff(&prod, None).unwrap(), vec![
+            VariableDiff::Encrypted("api_key".to_string()),
+            VariableDiff::Encrypted("password".to_string()),
+            VariableDiff::Encrypted("token".to_string()),
+        ]);
+
+        let (wrong_key, _) = SecretKey::generate();
+        assert!(matches!(dev.diff(&prod, Some(&wrong_key)), Err(ValidationError::Secret(_))));
+    }
 }
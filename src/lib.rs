@@ -2,28 +2,37 @@ pub mod cli;
 pub mod request;
 pub mod environment;
 pub mod response;
+pub mod runner;
 pub mod utils;
 
 use anyhow::{Context, Result};
-use cli::{Cli, Commands};
-use environment::EnvironmentResolver;
-use request::{RequestDefinition, RequestExecutor, RequestParser, RequestValidator};
+use cli::{Cli, Commands, EnvCommands};
+use colored::*;
+use environment::{
+    mask_value, rewrite_environment_variables, DynamicVariables, Environment, EnvironmentLocator,
+    EnvironmentResolver, SecretKey, VariableDiff, VariableValue,
+};
+use request::{RequestDefinition, RequestExecutor, RequestParser, RequestValidator, ValidationResult};
+use response::{AssertionEngine, ResponseFormatter};
+use runner::{CollectionRunner, Workflow, WorkflowRunner};
 
 pub async fn run(cli: Cli) -> Result<()> {
     match cli.command {
-        Commands::Run { path, env, output, verbose } => {
+        Commands::Run { path, env, output, verbose, concurrency, lenient, vars, seed } => {
             if verbose {
                 println!("🚀 Running request from: {}", path);
             }
 
             let mut env_resolver = EnvironmentResolver::default();
-            if let Some(env_file_path) = env {
+            let locator = EnvironmentLocator::default();
+            for env_name in &env {
+                let env_file_path = locator.find(env_name)?;
                 if verbose {
-                    println!("🌍 Loading environment from: {}", env_file_path);
+                    println!("🌍 Loading environment from: {}", env_file_path.display());
                 }
                 env_resolver
-                    .load_environment_file(env_file_path.as_str())
-                    .with_context(|| format!("Failed to load environment file: {}", env_file_path))?;
+                    .layer_environment_file(&env_file_path)
+                    .with_context(|| format!("Failed to load environment file: {}", env_file_path.display()))?;
                 if verbose {
                     if let Some(name) = env_resolver.active_environment_name() {
                         println!("  -> Environment '{}' loaded.", name);
@@ -31,36 +40,124 @@ pub async fn run(cli: Cli) -> Result<()> {
                         println!("  -> Environment loaded (name not available).");
                     }
                 }
-            } else if verbose {
+            }
+            if env.is_empty() && verbose {
                 println!("🌍 No environment file specified. Using default (empty) environment.");
             }
+            for (key, value) in vars {
+                env_resolver.set_cli_variable(key, value);
+            }
+            if let Some(seed) = seed {
+                env_resolver.set_dynamic_variables(DynamicVariables::with_seed(seed));
+            }
+            env_resolver.set_strict(!lenient);
+
+            if std::path::Path::new(&path).is_dir() {
+                if output.is_some() {
+                    anyhow::bail!("--output is only supported when running a single request file");
+                }
+
+                let request_executor = RequestExecutor::new();
+                let results = CollectionRunner::run_directory(&path, &env_resolver, &request_executor, concurrency).await;
+                CollectionRunner::print_run_results(&results, verbose);
+
+                let failed = results.iter().filter(|r| !r.passed()).count();
+                if failed > 0 {
+                    anyhow::bail!("{} of {} requests failed", failed, results.len());
+                }
+                return Ok(());
+            }
+
+            if Workflow::is_workflow_file(&path) {
+                if output.is_some() {
+                    anyhow::bail!("--output is only supported when running a single request file");
+                }
+
+                let workflow = Workflow::load(&path)
+                    .with_context(|| format!("Failed to parse workflow file: {}", path))?;
+                workflow.validate()
+                    .with_context(|| format!("Invalid workflow file: {}", path))?;
+                if verbose {
+                    println!("🔗 Running workflow '{}' ({} steps)", workflow.name, workflow.steps.len());
+                }
+
+                let request_executor = RequestExecutor::new();
+                let results = WorkflowRunner::run(&workflow, &env_resolver, &request_executor).await?;
+                CollectionRunner::print_run_results(&results, verbose);
+
+                let failed = results.iter().filter(|r| !r.passed()).count();
+                if failed > 0 {
+                    anyhow::bail!("{} of {} workflow steps failed", failed, results.len());
+                }
+                return Ok(());
+            }
 
             if verbose {
                 println!("📄 Parsing request file: {}", path);
             }
-            let raw_request_def: RequestDefinition = RequestParser::parse_file(&path)
+            let raw_request_def: RequestDefinition = RequestParser::parse_file_with_env(&path, &env_resolver)
                 .with_context(|| format!("Failed to parse request file: {}", path))?;
 
             if verbose {
                 println!("🔧 Resolving request definition with environment variables...");
+                let template = serde_json::to_string(&raw_request_def).unwrap_or_default();
+                for (name, source) in env_resolver.variable_sources(&template) {
+                    println!("  -> {{{{{}}}}} from {}", name, source);
+                }
             }
-            let resolved_request_def = raw_request_def.resolve_with_env(&env_resolver);
+            let resolved_request_def = raw_request_def
+                .resolve_with_env(&env_resolver)
+                .with_context(|| format!("Failed to resolve request: {}", path))?;
             if verbose {
-                println!("  -> Resolved Request: {:#?}", resolved_request_def);
+                println!("  -> Resolved Request: {:#?}", resolved_request_def.masked());
             }
 
             let request_executor = RequestExecutor::new();
             if verbose {
-                println!("⏳ Executing request: {}...", resolved_request_def.unwrap().name);
+                println!("⏳ Executing request: {}...", resolved_request_def.name);
             }
+            let result = request_executor
+                .execute(&resolved_request_def)
+                .await
+                .with_context(|| format!("Failed to execute request: {}", resolved_request_def.name))?;
+
+            println!("{}", ResponseFormatter::format_response(&result)?);
 
+            let assertions = resolved_request_def.tests.as_deref()
+                .map(|tests| AssertionEngine::evaluate_all(tests, &resolved_request_def.base_dir, &result))
+                .unwrap_or_default();
+            if !assertions.is_empty() {
+                println!("🧪 Tests:");
+                for assertion in &assertions {
+                    println!("  {}", assertion.format());
+                }
+            }
+
+            if let Some(output_path) = output {
+                std::fs::write(&output_path, &result.body)
+                    .with_context(|| format!("Failed to write response to: {}", output_path))?;
+                if verbose {
+                    println!("💾 Response saved to: {}", output_path);
+                }
+            }
+
+            let failed = assertions.iter().filter(|a| !a.passed).count();
+            if failed > 0 {
+                anyhow::bail!("{} of {} assertions failed", failed, assertions.len());
+            }
 
             Ok(())
         }
         Commands::Validate { path } => {
             let path_obj = std::path::Path::new(&path);
 
-            if path_obj.is_file() {
+            if path_obj.is_file() && Workflow::is_workflow_file(path_obj) {
+                let mut result = ValidationResult::new(path_obj.to_path_buf());
+                if let Err(error) = Workflow::load(path_obj).and_then(|workflow| workflow.validate()) {
+                    result.add_error(error);
+                }
+                RequestValidator::print_validation_results(&[result]);
+            } else if path_obj.is_file() {
                 let results = RequestValidator::validate_file(path_obj);
                 RequestValidator::print_validation_results(&[results]);
             } else if path_obj.is_dir() {
@@ -88,5 +185,132 @@ pub async fn run(cli: Cli) -> Result<()> {
 
             Ok(())
         }
+        Commands::Env { command } => run_env_command(command),
+    }
+}
+
+fn run_env_command(command: EnvCommands) -> Result<()> {
+    match command {
+        EnvCommands::List => {
+            let locator = EnvironmentLocator::default();
+            let entries = locator.list();
+            if entries.is_empty() {
+                let searched: Vec<_> = locator.search_dirs().iter().map(|dir| dir.display().to_string()).collect();
+                println!("  -> No environments found (searched {}).", searched.join(", "));
+            }
+            for entry in entries {
+                println!("  🌍 {} {} {}",
+                    entry.stem.bold(),
+                    format!("({})", entry.name).dimmed(),
+                    entry.path.display());
+            }
+            Ok(())
+        }
+        EnvCommands::Show { name } => {
+            let environment = load_named_environment(&name)?;
+            println!("🌍 {}", environment.name.bold().cyan());
+            for (variable, value) in environment.masked_variables() {
+                println!("  {}: {}", variable.bold(), value);
+            }
+            Ok(())
+        }
+        EnvCommands::Diff { left, right, key_file } => {
+            let left_env = load_named_environment(&left)?;
+            let right_env = load_named_environment(&right)?;
+            // Secrets can only be compared with the key; without one they are listed unchecked.
+            let key = match key_file {
+                Some(path) => Some(SecretKey::from_file(path)?),
+                None => SecretKey::from_env().ok(),
+            };
+            let lookup = |environment: &Environment, variable: &str| {
+                let value = environment.variables.as_ref()
+                    .and_then(|variables| variables.get(variable))
+                    .map(String::as_str)
+                    .unwrap_or_default();
+                mask_value(variable, value)
+            };
+
+            let differences = left_env.diff(&right_env, key.as_ref())
+                .with_context(|| format!("Failed to compare secrets of {} and {}", left, right))?;
+            println!("🔍 {} ↔ {}", left_env.name.bold(), right_env.name.bold());
+            if differences.is_empty() {
+                println!("  {}", "No differences".green());
+            }
+            for difference in differences {
+                match difference {
+                    VariableDiff::OnlyLeft(variable) => println!("  {} {} (only in {})",
+                        "-".red(), variable.red(), left_env.name),
+                    VariableDiff::OnlyRight(variable) => println!("  {} {} (only in {})",
+                        "+".green(), variable.green(), right_env.name),
+                    VariableDiff::Changed(variable) => println!("  {} {}: {} → {}",
+                        "~".yellow(), variable.yellow(),
+                        lookup(&left_env, &variable), lookup(&right_env, &variable)),
+                    VariableDiff::Encrypted(variable) => println!("  {} {}: {}",
+                        "?".dimmed(), variable.dimmed(), "encrypted (not compared)".dimmed()),
+                }
+            }
+            Ok(())
+        }
+        EnvCommands::Encrypt { file, names, key_file } => {
+            let key = secret_key(key_file.as_deref())?;
+            let changed = rewrite_environment_variables(&file, |name, value| match value {
+                VariableValue::Plain(text) if names.is_empty() || names.iter().any(|n| n == name) => {
+                    Ok(VariableValue::Secret(key.encrypt(&text)?))
+                }
+                other => Ok(other),
+            }).with_context(|| format!("Failed to encrypt variables in: {}", file))?;
+            println!("🔒 Encrypted {} variable(s) in {}", changed.to_string().green(), file);
+            Ok(())
+        }
+        EnvCommands::Decrypt { file, names, key_file } => {
+            let key = secret_key(key_file.as_deref())?;
+            let changed = rewrite_environment_variables(&file, |name, value| match value {
+                VariableValue::Secret(ciphertext) if names.is_empty() || names.iter().any(|n| n == name) => {
+                    Ok(VariableValue::Plain(key.decrypt(&ciphertext)?))
+                }
+                other => Ok(other),
+            }).with_context(|| format!("Failed to decrypt variables in: {}", file))?;
+            println!("🔓 Decrypted {} variable(s) in {}", changed.to_string().green(), file);
+            Ok(())
+        }
+        EnvCommands::Rotate { files, key_file, new_key_file } => {
+            let old_key = secret_key(key_file.as_deref())?;
+            let new_key = SecretKey::from_file(&new_key_file)?;
+            for file in &files {
+                let changed = rewrite_environment_variables(file, |_, value| match value {
+                    VariableValue::Secret(ciphertext) => {
+                        Ok(VariableValue::Secret(new_key.encrypt(&old_key.decrypt(&ciphertext)?)?))
+                    }
+                    other => Ok(other),
+                }).with_context(|| format!("Failed to rotate secrets in: {}", file))?;
+                println!("🔁 Re-encrypted {} secret(s) in {}", changed.to_string().green(), file);
+            }
+            Ok(())
+        }
+        EnvCommands::Keygen { path, force } => {
+            if std::path::Path::new(&path).exists() && !force {
+                anyhow::bail!("Key file already exists: {} (use --force to overwrite)", path);
+            }
+            let (_, encoded) = SecretKey::generate();
+            utils::write_private(&path, &format!("{}\n", encoded))
+                .with_context(|| format!("Failed to write key file: {}", path))?;
+            println!("🔑 New secret key written to {}; keep it out of version control", path);
+            Ok(())
+        }
     }
 }
+
+/// Loads an environment by name or path with its inherited variables;
+/// secrets stay encrypted so they can be shown masked.
+fn load_named_environment(name: &str) -> Result<Environment> {
+    let path = EnvironmentLocator::default().find(name)?;
+    Environment::load_raw(&path).with_context(|| format!("Failed to load environment file: {}", path.display()))
+}
+
+fn secret_key(key_file: Option<&str>) -> Result<SecretKey> {
+    let key = match key_file {
+        Some(path) => SecretKey::from_file(path)?,
+        None => SecretKey::from_env()?,
+    };
+    Ok(key)
+}
@@ -0,0 +1,256 @@
+use md5::Md5;
+use rand::RngCore;
+use reqwest::header::{HeaderMap, WWW_AUTHENTICATE};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::utils::hex_encode;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DigestAlgorithm {
+    Md5,
+    Md5Sess,
+    Sha256,
+    Sha256Sess,
+}
+
+impl DigestAlgorithm {
+    fn parse(name: &str) -> Option<Self> {
+        match name.to_ascii_uppercase().as_str() {
+            "MD5" => Some(DigestAlgorithm::Md5),
+            "MD5-SESS" => Some(DigestAlgorithm::Md5Sess),
+            "SHA-256" => Some(DigestAlgorithm::Sha256),
+            "SHA-256-SESS" => Some(DigestAlgorithm::Sha256Sess),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match self {
+            DigestAlgorithm::Md5 => "MD5",
+            DigestAlgorithm::Md5Sess => "MD5-sess",
+            DigestAlgorithm::Sha256 => "SHA-256",
+            DigestAlgorithm::Sha256Sess => "SHA-256-sess",
+        }
+    }
+
+    fn hash(&self, data: &str) -> String {
+        match self {
+            DigestAlgorithm::Md5 | DigestAlgorithm::Md5Sess => hex_encode(&Md5::digest(data.as_bytes())),
+            DigestAlgorithm::Sha256 | DigestAlgorithm::Sha256Sess => hex_encode(&Sha256::digest(data.as_bytes())),
+        }
+    }
+}
+
+/// The parameters of a `WWW-Authenticate: Digest ...` challenge.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DigestChallenge {
+    pub realm: String,
+    pub nonce: String,
+    pub opaque: Option<String>,
+    pub algorithm: DigestAlgorithm,
+    /// Whether the server offered `qop=auth`; without it the RFC 2069 form is used.
+    pub qop_auth: bool,
+}
+
+impl DigestChallenge {
+    /// The strongest Digest challenge among the response's
+    /// `WWW-Authenticate` headers, preferring SHA-256 over MD5.
+    pub fn from_headers(headers: &HeaderMap) -> Option<Self> {
+        let mut challenges: Vec<Self> = headers.get_all(WWW_AUTHENTICATE).iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(Self::parse)
+            .collect();
+        challenges.sort_by_key(|challenge| {
+            !matches!(challenge.algorithm, DigestAlgorithm::Sha256 | DigestAlgorithm::Sha256Sess)
+        });
+        challenges.into_iter().next()
+    }
+
+    pub fn parse(header: &str) -> Option<Self> {
+        let (scheme, params) = header.trim().split_once(' ')?;
+        if !scheme.eq_ignore_ascii_case("digest") {
+            return None;
+        }
+        let params = parse_params(params);
+
+        let algorithm = match params.get("algorithm") {
+            Some(name) => DigestAlgorithm::parse(name)?,
+            None => DigestAlgorithm::Md5,
+        };
+        let qop_auth = params.get("qop")
+            .is_some_and(|qop| qop.split(',').any(|option| option.trim().eq_ignore_ascii_case("auth")));
+
+        Some(Self {
+            realm: params.get("realm").cloned().unwrap_or_default(),
+            nonce: params.get("nonce")?.clone(),
+            opaque: params.get("opaque").cloned(),
+            algorithm,
+            qop_auth,
+        })
+    }
+
+    /// The `Authorization` header value for one request. `nc` counts the
+    /// requests made with this nonce, starting at 1.
+    pub fn authorization(
+        &self,
+        username: &str,
+        password: &str,
+        method: &str,
+        uri: &str,
+        nc: u32,
+        cnonce: &str,
+    ) -> String {
+        let hash = |data: String| self.algorithm.hash(&data);
+
+        let mut ha1 = hash(format!("{}:{}:{}", username, self.realm, password));
+        if matches!(self.algorithm, DigestAlgorithm::Md5Sess | DigestAlgorithm::Sha256Sess) {
+            ha1 = hash(format!("{}:{}:{}", ha1, self.nonce, cnonce));
+        }
+        let ha2 = hash(format!("{}:{}", method, uri));
+        let nc = format!("{:08x}", nc);
+
+        let response = if self.qop_auth {
+            hash(format!("{}:{}:{}:{}:auth:{}", ha1, self.nonce, nc, cnonce, ha2))
+        } else {
+            hash(format!("{}:{}:{}", ha1, self.nonce, ha2))
+        };
+
+        let mut header = format!(
+            "Digest username=\"{}\", realm=\"{}\", nonce=\"{}\", uri=\"{}\", algorithm={}, response=\"{}\"",
+            username, self.realm, self.nonce, uri, self.algorithm.name(), response
+        );
+        if self.qop_auth {
+            header.push_str(&format!(", qop=auth, nc={}, cnonce=\"{}\"", nc, cnonce));
+        }
+        if let Some(opaque) = &self.opaque {
+            header.push_str(&format!(", opaque=\"{}\"", opaque));
+        }
+        header
+    }
+}
+
+/// Splits `key=value, key="quoted, value"` pairs; keys are lowercased.
+fn parse_params(params: &str) -> HashMap<String, String> {
+    let mut result = HashMap::new();
+    let mut rest = params.trim();
+
+    while !rest.is_empty() {
+        let Some((key, after_key)) = rest.split_once('=') else { break };
+        let key = key.trim().trim_start_matches(',').trim().to_ascii_lowercase();
+        let after_key = after_key.trim_start();
+
+        let (value, remaining) = match after_key.strip_prefix('"') {
+            Some(quoted) => {
+                let mut value = String::new();
+                let mut chars = quoted.char_indices();
+                let mut end = quoted.len();
+                while let Some((offset, c)) = chars.next() {
+                    match c {
+                        '\\' => {
+                            if let Some((_, escaped)) = chars.next() {
+                                value.push(escaped);
+                            }
+                        }
+                        '"' => {
+                            end = offset + 1;
+                            break;
+                        }
+                        c => value.push(c),
+                    }
+                }
+                (value, &quoted[end.min(quoted.len())..])
+            }
+            None => match after_key.find(',') {
+                Some(comma) => (after_key[..comma].trim().to_string(), &after_key[comma..]),
+                None => (after_key.trim().to_string(), ""),
+            },
+        };
+
+        result.insert(key, value);
+        rest = remaining.trim_start().trim_start_matches(',').trim_start();
+    }
+
+    result
+}
+
+/// Digest challenges per origin for the current run, so later requests
+/// reuse the nonce instead of taking another 401 round trip.
+#[derive(Debug, Default)]
+pub struct DigestSessions {
+    sessions: Mutex<HashMap<String, (DigestChallenge, u32)>>,
+}
+
+impl DigestSessions {
+    pub fn start(&self, origin: &str, challenge: DigestChallenge) {
+        if let Ok(mut sessions) = self.sessions.lock() {
+            sessions.insert(origin.to_string(), (challenge, 0));
+        }
+    }
+
+    /// The next `Authorization` header for `origin`, counting the nonce use,
+    /// or `None` before the server has sent a challenge.
+    pub fn authorize(&self, origin: &str, username: &str, password: &str, method: &str, uri: &str) -> Option<String> {
+        let mut sessions = self.sessions.lock().ok()?;
+        let (challenge, nc) = sessions.get_mut(origin)?;
+        *nc += 1;
+
+        let mut cnonce = [0u8; 16];
+        rand::thread_rng().fill_bytes(&mut cnonce);
+        Some(challenge.authorization(username, password, method, uri, *nc, &hex_encode(&cnonce)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rfc_2617_example() {
+        let challenge = DigestChallenge::parse(
+            r#"Digest realm="testrealm@host.com", qop="auth,auth-int", nonce="dcd98b7102dd2f0e8b11d0f600bfb0c093", opaque="5ccc069c403ebaf9f0171e9517f40e41""#,
+        ).unwrap();
+        assert_eq!(challenge.algorithm, DigestAlgorithm::Md5);
+        assert!(challenge.qop_auth);
+
+        let header = challenge.authorization("Mufasa", "Circle Of Life", "GET", "/dir/index.html", 1, "0a4f113b");
+        assert_eq!(
+            header,
+            "Digest username=\"Mufasa\", realm=\"testrealm@host.com\", nonce=\"dcd98b7102dd2f0e8b11d0f600bfb0c093\", \
+             uri=\"/dir/index.html\", algorithm=MD5, response=\"6629fae49393a05397450978507c4ef1\", qop=auth, \
+             nc=00000001, cnonce=\"0a4f113b\", opaque=\"5ccc069c403ebaf9f0171e9517f40e41\""
+        );
+    }
+
+    #[test]
+    fn test_rfc_7616_examples() {
+        let challenge = |algorithm: &str| DigestChallenge::parse(&format!(
+            r#"Digest realm="http-auth@example.org", qop="auth, auth-int", algorithm={}, nonce="7ypf/xlj9XXwfDPEoM4URrv/xwf94BcCAzFZH4GiTo0v", opaque="FQhe/qaU925kfnzjCev0ciny7QMkPqMAFRtzCUYo5tdS""#,
+            algorithm
+        )).unwrap();
+        let cnonce = "f2/wE4q74E6zIJEtWaHKaf5wv/H5QzzpXusqGemxURZJ";
+
+        let sha256 = challenge("SHA-256").authorization("Mufasa", "Circle of Life", "GET", "/dir/index.html", 1, cnonce);
+        assert!(sha256.contains("response=\"753927fa0e85d155564e2e272a28d1802ca10daf4496794697cf8db5856cb6c1\""));
+
+        let md5 = challenge("MD5").authorization("Mufasa", "Circle of Life", "GET", "/dir/index.html", 1, cnonce);
+        assert!(md5.contains("response=\"8ca523f5e9506fed4657c9700eebdbec\""));
+    }
+
+    #[test]
+    fn test_from_headers_prefers_sha256_and_sessions_count_nonce_uses() {
+        let mut headers = HeaderMap::new();
+        headers.append(WWW_AUTHENTICATE, "Basic realm=\"x\"".parse().unwrap());
+        headers.append(WWW_AUTHENTICATE, "Digest realm=\"r\", nonce=\"n\", algorithm=MD5, qop=\"auth\"".parse().unwrap());
+        headers.append(WWW_AUTHENTICATE, "Digest realm=\"r\", nonce=\"n\", algorithm=SHA-256, qop=\"auth\"".parse().unwrap());
+        let challenge = DigestChallenge::from_headers(&headers).unwrap();
+        assert_eq!(challenge.algorithm, DigestAlgorithm::Sha256);
+
+        let sessions = DigestSessions::default();
+        assert!(sessions.authorize("http://host", "u", "p", "GET", "/").is_none());
+        sessions.start("http://host", challenge);
+        assert!(sessions.authorize("http://host", "u", "p", "GET", "/").unwrap().contains("nc=00000001"));
+        assert!(sessions.authorize("http://host", "u", "p", "GET", "/").unwrap().contains("nc=00000002"));
+    }
+}
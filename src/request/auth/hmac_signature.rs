@@ -0,0 +1,177 @@
+use anyhow::{Context, Result};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use regex::{Captures, Regex};
+use reqwest::header::{HeaderName, HeaderValue};
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+
+use crate::request::{HmacAlgorithm, HmacConfig, SignatureEncoding};
+use crate::utils::hex_encode;
+
+/// String signed when the request file doesn't give a `canonical` template.
+pub const DEFAULT_CANONICAL_TEMPLATE: &str = "{method}\n{path}\n{timestamp}\n{body_hash}";
+pub const DEFAULT_SIGNATURE_HEADER: &str = "X-Signature";
+
+/// Signs `request` with an HMAC over its canonical string and sets the
+/// signature header, plus the timestamp header when one is configured.
+///
+/// The canonical template may use `{method}`, `{path}`, `{query}`, `{host}`,
+/// `{timestamp}` (Unix seconds) and `{body_hash}` (the body digested with the
+/// same algorithm, in the same encoding as the signature).
+pub fn sign_hmac(request: &mut reqwest::Request, config: &HmacConfig, timestamp: i64) -> Result<()> {
+    let body = super::signable_body(request, "an HMAC signature")?;
+    let encoding = config.encoding.unwrap_or_default();
+    let body_hash = encode(&digest(config.algorithm, body), encoding);
+
+    // Placeholders are filled in one pass so a `{...}` inside the path or
+    // query is signed as written rather than substituted again.
+    let template = config.canonical.as_deref().unwrap_or(DEFAULT_CANONICAL_TEMPLATE);
+    let placeholder = Regex::new(r"\{(method|path|query|host|timestamp|body_hash)\}").unwrap(); // Infallible regex
+    let canonical = placeholder.replace_all(template, |captures: &Captures| match &captures[1] {
+        "method" => request.method().to_string(),
+        "path" => request.url().path().to_string(),
+        "query" => request.url().query().unwrap_or_default().to_string(),
+        "host" => request.url().host_str().unwrap_or_default().to_string(),
+        "timestamp" => timestamp.to_string(),
+        _ => body_hash.clone(),
+    });
+
+    let signature = encode(&hmac(config.algorithm, config.key.as_bytes(), canonical.as_bytes()), encoding);
+
+    let header = config.header.as_deref().unwrap_or(DEFAULT_SIGNATURE_HEADER);
+    let mut headers = vec![(header, format!("{}{}", config.prefix.as_deref().unwrap_or_default(), signature))];
+    if let Some(timestamp_header) = &config.timestamp_header {
+        headers.push((timestamp_header, timestamp.to_string()));
+    }
+    for (name, value) in headers {
+        let name = HeaderName::from_bytes(name.as_bytes()).with_context(|| format!("Invalid header name '{}'", name))?;
+        let value = HeaderValue::from_str(&value).with_context(|| format!("Invalid value for header {}", name))?;
+        request.headers_mut().insert(name, value);
+    }
+    Ok(())
+}
+
+fn digest(algorithm: HmacAlgorithm, data: &[u8]) -> Vec<u8> {
+    match algorithm {
+        HmacAlgorithm::Sha1 => Sha1::digest(data).to_vec(),
+        HmacAlgorithm::Sha256 => Sha256::digest(data).to_vec(),
+        HmacAlgorithm::Sha512 => Sha512::digest(data).to_vec(),
+    }
+}
+
+fn hmac(algorithm: HmacAlgorithm, key: &[u8], data: &[u8]) -> Vec<u8> {
+    const ANY_KEY: &str = "HMAC accepts keys of any length";
+    match algorithm {
+        HmacAlgorithm::Sha1 => {
+            let mut mac = Hmac::<Sha1>::new_from_slice(key).expect(ANY_KEY);
+            mac.update(data);
+            mac.finalize().into_bytes().to_vec()
+        }
+        HmacAlgorithm::Sha256 => {
+            let mut mac = Hmac::<Sha256>::new_from_slice(key).expect(ANY_KEY);
+            mac.update(data);
+            mac.finalize().into_bytes().to_vec()
+        }
+        HmacAlgorithm::Sha512 => {
+            let mut mac = Hmac::<Sha512>::new_from_slice(key).expect(ANY_KEY);
+            mac.update(data);
+            mac.finalize().into_bytes().to_vec()
+        }
+    }
+}
+
+fn encode(bytes: &[u8], encoding: SignatureEncoding) -> String {
+    match encoding {
+        SignatureEncoding::Hex => hex_encode(bytes),
+        SignatureEncoding::Base64 => BASE64.encode(bytes),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::Client;
+
+    fn config(algorithm: HmacAlgorithm) -> HmacConfig {
+        HmacConfig {
+            algorithm,
+            key: "key".to_string(),
+            canonical: None,
+            encoding: None,
+            header: None,
+            prefix: None,
+            timestamp_header: None,
+        }
+    }
+
+    #[test]
+    fn test_rfc_4231_and_wikipedia_vectors() {
+        let message = b"The quick brown fox jumps over the lazy dog";
+        assert_eq!(hex_encode(&hmac(HmacAlgorithm::Sha1, b"key", message)), "de7c9b85b8b78aa6bc8a7a36f70a90701c9db4d9");
+        assert_eq!(
+            hex_encode(&hmac(HmacAlgorithm::Sha256, b"key", message)),
+            "f7bc83f430538424b13298e6aa6fb143ef4d59a14946175997479dbc2d1a3cd8"
+        );
+        // RFC 4231 test case 2
+        assert_eq!(
+            hex_encode(&hmac(HmacAlgorithm::Sha512, b"Jefe", b"what do ya want for nothing?")),
+            "164b7a7bfcf819e2e395fbe73b56e0a387bd64222e831fd610270cd7ea2505549758bf75c05a994a6d034f65f8f0e6fdcaeab1a34d4a6b4b636e070a38bce737"
+        );
+    }
+
+    #[test]
+    fn test_sign_hmac_default_canonical_string() {
+        let mut request = Client::new().post("https://partner.example.com/hooks?x=1").body("{}").build().unwrap();
+        sign_hmac(&mut request, &config(HmacAlgorithm::Sha256), 1700000000).unwrap();
+
+        let body_hash = hex_encode(&Sha256::digest(b"{}"));
+        let canonical = format!("POST\n/hooks\n1700000000\n{}", body_hash);
+        let expected = hex_encode(&hmac(HmacAlgorithm::Sha256, b"key", canonical.as_bytes()));
+        assert_eq!(request.headers()["x-signature"], expected.as_str());
+    }
+
+    #[test]
+    fn test_sign_hmac_custom_template_header_and_base64() {
+        let mut request = Client::new().get("https://partner.example.com/orders?page=2").build().unwrap();
+        let config = HmacConfig {
+            canonical: Some("{timestamp}.{method}.{host}{path}?{query}".to_string()),
+            encoding: Some(SignatureEncoding::Base64),
+            header: Some("X-Partner-Signature".to_string()),
+            prefix: Some("v1=".to_string()),
+            timestamp_header: Some("X-Partner-Timestamp".to_string()),
+            ..config(HmacAlgorithm::Sha512)
+        };
+        sign_hmac(&mut request, &config, 42).unwrap();
+
+        let expected = BASE64.encode(hmac(HmacAlgorithm::Sha512, b"key", b"42.GET.partner.example.com/orders?page=2"));
+        assert_eq!(request.headers()["x-partner-signature"], format!("v1={}", expected).as_str());
+        assert_eq!(request.headers()["x-partner-timestamp"], "42");
+    }
+
+    #[test]
+    fn test_sign_hmac_leaves_placeholders_in_the_query_alone() {
+        let mut request = Client::new().get("https://partner.example.com/search?q={timestamp}&h={body_hash}").build().unwrap();
+        let config = HmacConfig {
+            canonical: Some("{query}|{timestamp}".to_string()),
+            ..config(HmacAlgorithm::Sha256)
+        };
+        sign_hmac(&mut request, &config, 42).unwrap();
+
+        let expected = hex_encode(&hmac(HmacAlgorithm::Sha256, b"key", b"q={timestamp}&h={body_hash}|42"));
+        assert_eq!(request.headers()["x-signature"], expected.as_str());
+    }
+
+    #[test]
+    fn test_sign_hmac_rejects_streamed_body() {
+        let mut request = Client::new()
+            .post("https://partner.example.com/uploads")
+            .multipart(reqwest::multipart::Form::new().text("field", "value"))
+            .build()
+            .unwrap();
+        let error = sign_hmac(&mut request, &config(HmacAlgorithm::Sha256), 42).unwrap_err();
+        assert!(error.to_string().contains("streamed body"));
+        assert!(request.headers().get("x-signature").is_none());
+    }
+}
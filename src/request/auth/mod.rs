@@ -0,0 +1,22 @@
+pub mod oauth2;
+pub mod sigv4;
+pub mod digest;
+pub mod hmac_signature;
+pub mod jwt;
+pub use oauth2::*;
+pub use sigv4::*;
+pub use digest::*;
+pub use hmac_signature::*;
+pub use jwt::*;
+
+/// The body bytes a signature covers; empty when the request has none.
+/// Streamed bodies, such as multipart uploads, cannot be hashed up front
+/// and are an error naming `scheme`.
+pub(crate) fn signable_body<'a>(request: &'a reqwest::Request, scheme: &str) -> anyhow::Result<&'a [u8]> {
+    match request.body() {
+        Some(body) => body.as_bytes().ok_or_else(|| {
+            anyhow::anyhow!("Cannot sign a streamed body (such as a multipart upload) with {}", scheme)
+        }),
+        None => Ok(&[]),
+    }
+}
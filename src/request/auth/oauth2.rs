@@ -0,0 +1,296 @@
+use anyhow::{Context, Result};
+use reqwest::Client;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::request::{OAuth2Config, OAuth2GrantType};
+use crate::utils::write_private;
+
+/// A token is refreshed this many seconds before it expires.
+pub const EXPIRY_MARGIN_SECS: u64 = 30;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OAuth2Token {
+    pub access_token: String,
+    pub refresh_token: Option<String>,
+    /// Unix time in seconds; `None` when the server gave no `expires_in`.
+    pub expires_at: Option<u64>,
+}
+
+impl OAuth2Token {
+    pub fn is_fresh(&self) -> bool {
+        self.expires_at.is_none_or(|expires_at| unix_now() + EXPIRY_MARGIN_SECS < expires_at)
+    }
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: Option<String>,
+    expires_in: Option<u64>,
+}
+
+/// Access tokens by client and grant. With a file, tokens that have an
+/// expiry are kept across invocations.
+#[derive(Debug, Default)]
+pub struct OAuth2TokenCache {
+    path: Option<PathBuf>,
+    tokens: Mutex<HashMap<String, OAuth2Token>>,
+}
+
+impl OAuth2TokenCache {
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// A cache backed by `path`; an unreadable file starts an empty cache.
+    pub fn persistent<P: AsRef<Path>>(path: P) -> Self {
+        let path = path.as_ref().to_path_buf();
+        let tokens = std::fs::read_to_string(&path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+        Self { path: Some(path), tokens: Mutex::new(tokens) }
+    }
+
+    /// `rpcli/oauth2-tokens.json` in the user cache dir.
+    pub fn default_path() -> Option<PathBuf> {
+        dirs::cache_dir().map(|dir| dir.join("rpcli").join("oauth2-tokens.json"))
+    }
+
+    pub fn get(&self, key: &str) -> Option<OAuth2Token> {
+        self.tokens.lock().ok()?.get(key).cloned()
+    }
+
+    pub fn store(&self, key: &str, token: OAuth2Token) {
+        let Ok(mut tokens) = self.tokens.lock() else { return };
+        tokens.insert(key.to_string(), token);
+        if let Some(path) = &self.path {
+            let persisted: HashMap<_, _> = tokens.iter()
+                .filter(|(_, token)| token.expires_at.is_some())
+                .collect();
+            // Failing to persist only costs a token request next time.
+            let _ = write_private(path, &serde_json::to_string_pretty(&persisted).unwrap_or_default());
+        }
+    }
+}
+
+/// Fetches access tokens for `AuthConfig::OAuth2`, going through the cache.
+pub struct OAuth2Client<'a> {
+    client: &'a Client,
+    cache: &'a OAuth2TokenCache,
+}
+
+impl<'a> OAuth2Client<'a> {
+    pub fn new(client: &'a Client, cache: &'a OAuth2TokenCache) -> Self {
+        Self { client, cache }
+    }
+
+    /// A cached token while it is fresh, otherwise a new one.
+    pub async fn access_token(&self, config: &OAuth2Config) -> Result<String> {
+        if let Some(token) = self.cache.get(&cache_key(config)) {
+            if token.is_fresh() {
+                return Ok(token.access_token);
+            }
+        }
+        self.renew(config).await
+    }
+
+    /// Gets a new token even if the cached one looks fresh, e.g. after the
+    /// server rejected it. A cached refresh token is tried first; when that
+    /// fails, the configured grant is used.
+    pub async fn renew(&self, config: &OAuth2Config) -> Result<String> {
+        let key = cache_key(config);
+        let cached_refresh = self.cache.get(&key).and_then(|token| token.refresh_token);
+
+        let token = match cached_refresh {
+            Some(refresh_token) => match self.request_token(config, OAuth2GrantType::RefreshToken, Some(&refresh_token)).await {
+                Ok(token) => token,
+                Err(_) => self.request_token(config, config.grant_type, config.refresh_token.as_deref()).await?,
+            },
+            None => self.request_token(config, config.grant_type, config.refresh_token.as_deref()).await?,
+        };
+
+        let access_token = token.access_token.clone();
+        self.cache.store(&key, token);
+        Ok(access_token)
+    }
+
+    async fn request_token(
+        &self,
+        config: &OAuth2Config,
+        grant_type: OAuth2GrantType,
+        refresh_token: Option<&str>,
+    ) -> Result<OAuth2Token> {
+        let mut form = vec![("grant_type", grant_type_name(grant_type).to_string())];
+        form.push(("client_id", config.client_id.clone()));
+        if let Some(secret) = &config.client_secret {
+            form.push(("client_secret", secret.clone()));
+        }
+        if let Some(scopes) = config.scopes.as_ref().filter(|scopes| !scopes.is_empty()) {
+            form.push(("scope", scopes.join(" ")));
+        }
+        if let Some(audience) = &config.audience {
+            form.push(("audience", audience.clone()));
+        }
+        match grant_type {
+            OAuth2GrantType::ClientCredentials => {}
+            OAuth2GrantType::Password => {
+                form.push(("username", config.username.clone().unwrap_or_default()));
+                form.push(("password", config.password.clone().unwrap_or_default()));
+            }
+            OAuth2GrantType::RefreshToken => {
+                form.push(("refresh_token", refresh_token.unwrap_or_default().to_string()));
+            }
+        }
+
+        let response = self.client.post(&config.token_url)
+            .form(&form)
+            .send()
+            .await
+            .with_context(|| format!("Failed to request OAuth2 token from {}", config.token_url))?;
+
+        let status = response.status();
+        let body = response.text().await.unwrap_or_default();
+        if !status.is_success() {
+            anyhow::bail!("OAuth2 token request to {} failed with {}: {}", config.token_url, status, body);
+        }
+
+        let token: TokenResponse = serde_json::from_str(&body)
+            .with_context(|| format!("Invalid OAuth2 token response from {}", config.token_url))?;
+        Ok(OAuth2Token {
+            access_token: token.access_token,
+            // Servers may omit the refresh token on a refresh; keep using the old one.
+            refresh_token: token.refresh_token.or_else(|| refresh_token.map(str::to_string)),
+            expires_at: token.expires_in.map(|expires_in| unix_now() + expires_in),
+        })
+    }
+}
+
+fn grant_type_name(grant_type: OAuth2GrantType) -> &'static str {
+    match grant_type {
+        OAuth2GrantType::ClientCredentials => "client_credentials",
+        OAuth2GrantType::Password => "password",
+        OAuth2GrantType::RefreshToken => "refresh_token",
+    }
+}
+
+/// Identifies whose token it is; secrets are left out so the cache file
+/// doesn't hold them.
+fn cache_key(config: &OAuth2Config) -> String {
+    format!(
+        "{} {} {} {} {} {}",
+        grant_type_name(config.grant_type),
+        config.token_url,
+        config.client_id,
+        config.scopes.as_deref().unwrap_or_default().join(","),
+        config.audience.as_deref().unwrap_or_default(),
+        config.username.as_deref().unwrap_or_default(),
+    )
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wiremock::matchers::{body_string_contains, method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn config(token_url: String) -> OAuth2Config {
+        OAuth2Config {
+            grant_type: OAuth2GrantType::ClientCredentials,
+            token_url,
+            client_id: "cli".to_string(),
+            client_secret: Some("s3cret".to_string()),
+            scopes: Some(vec!["read".to_string(), "write".to_string()]),
+            audience: Some("https://api".to_string()),
+            username: None,
+            password: None,
+            refresh_token: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_access_token_is_cached_until_close_to_expiry() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains("grant_type=client_credentials"))
+            .and(body_string_contains("scope=read+write"))
+            .and(body_string_contains("audience=https%3A%2F%2Fapi"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"access_token":"t1","expires_in":3600}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let temp_dir = tempfile::Builder::new().prefix("test_oauth2_cache_").tempdir().unwrap();
+        let cache_path = temp_dir.path().join("tokens.json");
+        let config = config(format!("{}/token", server.uri()));
+        let client = Client::new();
+
+        let cache = OAuth2TokenCache::persistent(&cache_path);
+        let oauth = OAuth2Client::new(&client, &cache);
+        assert_eq!(oauth.access_token(&config).await.unwrap(), "t1");
+        assert_eq!(oauth.access_token(&config).await.unwrap(), "t1");
+
+        // A new invocation reads the token from the cache file.
+        let cache = OAuth2TokenCache::persistent(&cache_path);
+        assert_eq!(OAuth2Client::new(&client, &cache).access_token(&config).await.unwrap(), "t1");
+        assert!(!std::fs::read_to_string(&cache_path).unwrap().contains("s3cret"));
+    }
+
+    #[tokio::test]
+    async fn test_token_expiring_within_margin_is_renewed() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"access_token":"short","expires_in":10}"#))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let client = Client::new();
+        let cache = OAuth2TokenCache::in_memory();
+        let oauth = OAuth2Client::new(&client, &cache);
+        let config = config(format!("{}/token", server.uri()));
+        oauth.access_token(&config).await.unwrap();
+        oauth.access_token(&config).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_password_grant_and_token_errors() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains("grant_type=password"))
+            .and(body_string_contains("username=ann"))
+            .and(body_string_contains("password=pw"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"access_token":"user-token"}"#))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/denied"))
+            .respond_with(ResponseTemplate::new(400).set_body_string(r#"{"error":"invalid_client"}"#))
+            .mount(&server)
+            .await;
+
+        let client = Client::new();
+        let cache = OAuth2TokenCache::in_memory();
+        let oauth = OAuth2Client::new(&client, &cache);
+
+        let mut password = config(format!("{}/token", server.uri()));
+        password.grant_type = OAuth2GrantType::Password;
+        password.username = Some("ann".to_string());
+        password.password = Some("pw".to_string());
+        assert_eq!(oauth.access_token(&password).await.unwrap(), "user-token");
+
+        let error = oauth.access_token(&config(format!("{}/denied", server.uri()))).await.unwrap_err();
+        assert!(error.to_string().contains("invalid_client"), "{}", error);
+    }
+}
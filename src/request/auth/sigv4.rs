@@ -0,0 +1,248 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use reqwest::header::{HeaderName, HeaderValue, AUTHORIZATION, HOST};
+use sha2::{Digest, Sha256};
+
+use crate::request::AwsSigV4Config;
+use crate::utils::{hex_encode, percent_decode, percent_encode};
+
+const ALGORITHM: &str = "AWS4-HMAC-SHA256";
+
+/// Signs `request` with AWS Signature Version 4.
+///
+/// Adds `Host`, `X-Amz-Date`, `X-Amz-Security-Token` (with a session
+/// token), `X-Amz-Content-Sha256` (for S3) and `Authorization`. Every header
+/// already on the request is signed, as are the query string and the body
+/// hash, so this has to run on the final request.
+pub fn sign_aws_sigv4(request: &mut reqwest::Request, config: &AwsSigV4Config, now: DateTime<Utc>) -> Result<()> {
+    let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let date = now.format("%Y%m%d").to_string();
+    let body = super::signable_body(request, "AWS SigV4")?;
+    let payload_hash = hex_encode(&Sha256::digest(body));
+
+    let host = match (request.url().host_str(), request.url().port()) {
+        (Some(host), Some(port)) => format!("{}:{}", host, port),
+        (Some(host), None) => host.to_string(),
+        (None, _) => anyhow::bail!("Cannot sign a request without a host: {}", request.url()),
+    };
+
+    let mut added = vec![(HOST, host), (HeaderName::from_static("x-amz-date"), amz_date.clone())];
+    if let Some(token) = &config.session_token {
+        added.push((HeaderName::from_static("x-amz-security-token"), token.clone()));
+    }
+    if config.service == "s3" {
+        added.push((HeaderName::from_static("x-amz-content-sha256"), payload_hash.clone()));
+    }
+    for (name, value) in added {
+        let value = HeaderValue::from_str(&value).with_context(|| format!("Invalid value for header {}", name))?;
+        request.headers_mut().insert(name, value);
+    }
+
+    let (signed_headers, canonical_headers) = canonical_headers(request);
+    let canonical_request = format!(
+        "{}\n{}\n{}\n{}\n{}\n{}",
+        request.method().as_str(),
+        canonical_uri(request.url().path(), &config.service),
+        canonical_query(request.url().query().unwrap_or_default()),
+        canonical_headers,
+        signed_headers,
+        payload_hash,
+    );
+
+    let scope = format!("{}/{}/{}/aws4_request", date, config.region, config.service);
+    let string_to_sign = format!(
+        "{}\n{}\n{}\n{}",
+        ALGORITHM, amz_date, scope, hex_encode(&Sha256::digest(canonical_request.as_bytes()))
+    );
+
+    let mut key = hmac_sha256(format!("AWS4{}", config.secret_key).as_bytes(), date.as_bytes());
+    for part in [config.region.as_str(), config.service.as_str(), "aws4_request"] {
+        key = hmac_sha256(&key, part.as_bytes());
+    }
+    let signature = hex_encode(&hmac_sha256(&key, string_to_sign.as_bytes()));
+
+    let authorization = format!(
+        "{} Credential={}/{}, SignedHeaders={}, Signature={}",
+        ALGORITHM, config.access_key, scope, signed_headers, signature
+    );
+    request.headers_mut().insert(
+        AUTHORIZATION,
+        HeaderValue::from_str(&authorization).context("Invalid access key for the Authorization header")?,
+    );
+    Ok(())
+}
+
+/// Each path segment URI-encoded twice, or once for S3; an empty path is `/`.
+fn canonical_uri(path: &str, service: &str) -> String {
+    if path.is_empty() {
+        return "/".to_string();
+    }
+    path.split('/')
+        .map(|segment| {
+            let encoded = percent_encode(&percent_decode(segment));
+            if service == "s3" { encoded } else { percent_encode(&encoded) }
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Parameters encoded and sorted by name, then value.
+fn canonical_query(query: &str) -> String {
+    let mut params: Vec<(String, String)> = query.split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let decode = |text: &str| percent_decode(&text.replace('+', " "));
+            (percent_encode(&decode(name)), percent_encode(&decode(value)))
+        })
+        .collect();
+    params.sort();
+    params.iter()
+        .map(|(name, value)| format!("{}={}", name, value))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// The `SignedHeaders` list and the canonical header block: lowercase names,
+/// sorted, with values trimmed and inner runs of spaces collapsed.
+fn canonical_headers(request: &reqwest::Request) -> (String, String) {
+    let mut names: Vec<&str> = request.headers().keys().map(|name| name.as_str()).collect();
+    names.sort();
+    names.dedup();
+
+    let canonical = names.iter().map(|name| {
+        let values: Vec<String> = request.headers().get_all(*name).iter()
+            .map(|value| {
+                String::from_utf8_lossy(value.as_bytes())
+                    .split_whitespace()
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect();
+        format!("{}:{}\n", name, values.join(","))
+    }).collect::<String>();
+
+    (names.join(";"), canonical)
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use reqwest::{Client, Method};
+
+    // Credentials and timestamp shared by the AWS SigV4 test suite.
+    fn config(service: &str) -> AwsSigV4Config {
+        AwsSigV4Config {
+            access_key: "AKIDEXAMPLE".to_string(),
+            secret_key: "wJalrXUtnFEMI/K7MDENG+bPxRfiCYEXAMPLEKEY".to_string(),
+            session_token: None,
+            region: "us-east-1".to_string(),
+            service: service.to_string(),
+        }
+    }
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2015, 8, 30, 12, 36, 0).unwrap()
+    }
+
+    fn signed(mut request: reqwest::Request, service: &str) -> String {
+        sign_aws_sigv4(&mut request, &config(service), now()).unwrap();
+        request.headers()[AUTHORIZATION].to_str().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_get_vanilla() {
+        let request = Client::new().get("https://example.amazonaws.com/").build().unwrap();
+        assert_eq!(
+            signed(request, "service"),
+            "AWS4-HMAC-SHA256 Credential=AKIDEXAMPLE/20150830/us-east-1/service/aws4_request, \
+             SignedHeaders=host;x-amz-date, \
+             Signature=5fa00fa31553b73ebf1942676e86291e8372ff2a2260956d9b8aae1d763fbf31"
+        );
+    }
+
+    #[test]
+    fn test_get_vanilla_query_order_key_case() {
+        let request = Client::new()
+            .get("https://example.amazonaws.com/?Param2=value2&Param1=value1")
+            .build()
+            .unwrap();
+        assert!(signed(request, "service")
+            .ends_with("Signature=b97d918cfa904a5beff61c982a1b6f458b799221646efd99d3219ec94cdf2500"));
+    }
+
+    #[test]
+    fn test_post_vanilla() {
+        let request = Client::new().request(Method::POST, "https://example.amazonaws.com/").build().unwrap();
+        assert!(signed(request, "service")
+            .ends_with("Signature=5da7c1a2acd57cee7505fc6676e4e544621c30862966e37dddb68e92efbe5d6b"));
+    }
+
+    #[test]
+    fn test_post_x_www_form_urlencoded() {
+        let request = Client::new()
+            .post("https://example.amazonaws.com/")
+            .header("Content-Type", "application/x-www-form-urlencoded")
+            .body("Param1=value1")
+            .build()
+            .unwrap();
+        let authorization = signed(request, "service");
+        assert!(authorization.contains("SignedHeaders=content-type;host;x-amz-date,"));
+        assert!(authorization.ends_with("Signature=ff11897932ad3f4e8b18135d722051e5ac45fc38421b1da7b9d196a0fe09473a"));
+    }
+
+    #[test]
+    fn test_iam_list_users_documentation_example() {
+        let request = Client::new()
+            .get("https://iam.amazonaws.com/?Action=ListUsers&Version=2010-05-08")
+            .header("Content-Type", "application/x-www-form-urlencoded; charset=utf-8")
+            .build()
+            .unwrap();
+        assert!(signed(request, "iam")
+            .ends_with("Signature=5d672d79c15b13162d9279b0855cfba6789a8edb4c82c400e06b5924a6f2b5d7"));
+    }
+
+    #[test]
+    fn test_session_token_and_s3_payload_hash_headers() {
+        let mut request = Client::new().put("https://bucket.s3.amazonaws.com/a%20b.txt").body("hi").build().unwrap();
+        let mut config = config("s3");
+        config.session_token = Some("session".to_string());
+        sign_aws_sigv4(&mut request, &config, now()).unwrap();
+
+        assert_eq!(request.headers()["x-amz-security-token"], "session");
+        assert_eq!(
+            request.headers()["x-amz-content-sha256"],
+            "8f434346648f6b96df89dda901c5176b10a6d83961dd3c1ac88b59b2dc327aa4"
+        );
+        assert!(request.headers()[AUTHORIZATION].to_str().unwrap()
+            .contains("SignedHeaders=host;x-amz-content-sha256;x-amz-date;x-amz-security-token,"));
+        assert_eq!(canonical_uri("/a%20b.txt", "s3"), "/a%20b.txt");
+        assert_eq!(canonical_query("b=2&a=x+y&a=%2F"), "a=%2F&a=x%20y&b=2");
+    }
+
+    #[test]
+    fn test_encoded_path_segments_are_double_encoded_outside_s3() {
+        assert_eq!(canonical_uri("/users/a%20b/caf%C3%A9", "execute-api"), "/users/a%2520b/caf%25C3%25A9");
+        assert_eq!(canonical_uri("/users/a%20b/caf%C3%A9", "s3"), "/users/a%20b/caf%C3%A9");
+        assert_eq!(canonical_uri("/users/plain-id", "execute-api"), "/users/plain-id");
+    }
+
+    #[test]
+    fn test_streamed_body_is_an_error() {
+        let mut request = Client::new()
+            .post("https://example.amazonaws.com/")
+            .multipart(reqwest::multipart::Form::new().text("field", "value"))
+            .build()
+            .unwrap();
+        let error = sign_aws_sigv4(&mut request, &config("service"), now()).unwrap_err();
+        assert!(error.to_string().contains("streamed body"));
+    }
+}
@@ -1,20 +1,467 @@
-use anyhow::Result;
-use reqwest::Client;
-use crate::{environment::EnvironmentResolver, request::RequestDefinition};
+use anyhow::{Context, Result};
+use chrono::Utc;
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::{Client, Method, StatusCode};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-#[derive(Default)]
+use crate::request::{
+    mint_jwt, sign_aws_sigv4, sign_hmac, ApiKeyLocation, AuthConfig, DigestChallenge, DigestSessions, HttpMethod,
+    OAuth2Client, OAuth2TokenCache, RequestBody, RequestDefinition,
+};
+
+/// Everything we keep from a response once the request has been sent.
+#[derive(Debug, Clone)]
+pub struct ExecutionResult {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Vec<u8>,
+    pub url: String,
+    pub elapsed: Duration,
+}
+
+impl ExecutionResult {
+    pub fn body_text(&self) -> String {
+        String::from_utf8_lossy(&self.body).into_owned()
+    }
+
+    pub fn body_json(&self) -> Option<serde_json::Value> {
+        serde_json::from_slice(&self.body).ok()
+    }
+}
+
+impl From<&HttpMethod> for Method {
+    fn from(method: &HttpMethod) -> Self {
+        match method {
+            HttpMethod::GET => Method::GET,
+            HttpMethod::POST => Method::POST,
+            HttpMethod::PUT => Method::PUT,
+            HttpMethod::PATCH => Method::PATCH,
+            HttpMethod::DELETE => Method::DELETE,
+            HttpMethod::HEAD => Method::HEAD,
+            HttpMethod::OPTIONS => Method::OPTIONS,
+        }
+    }
+}
+
+#[derive(Default, Clone)]
 pub struct RequestExecutor {
     client: Client,
+    token_cache: Arc<OAuth2TokenCache>,
+    digest_sessions: Arc<DigestSessions>,
 }
 
 impl RequestExecutor {
+    /// An executor whose OAuth2 tokens are cached in the user cache dir.
     pub fn new() -> Self {
+        let token_cache = match OAuth2TokenCache::default_path() {
+            Some(path) => OAuth2TokenCache::persistent(path),
+            None => OAuth2TokenCache::in_memory(),
+        };
+        Self::with_token_cache(token_cache)
+    }
+
+    pub fn with_token_cache(token_cache: OAuth2TokenCache) -> Self {
         Self {
             client: Client::new(),
+            token_cache: Arc::new(token_cache),
+            digest_sessions: Arc::default(),
+        }
+    }
+
+    /// Sends an already resolved request and collects the response.
+    ///
+    /// OAuth2 requests get a token first; when the server answers 401, the
+    /// token is renewed and the request sent once more. Signing schemes run
+    /// last, on the request exactly as it will be sent.
+    pub async fn execute(&self, request: &RequestDefinition) -> Result<ExecutionResult> {
+        let mut http_request = self.build(request).await?;
+
+        match &request.auth {
+            Some(AuthConfig::AwsSigV4(config)) => sign_aws_sigv4(&mut http_request, config, Utc::now())?,
+            Some(AuthConfig::Hmac(config)) => sign_hmac(&mut http_request, config, Utc::now().timestamp())?,
+            _ => {}
+        }
+
+        if let Some(AuthConfig::Digest { username, password }) = &request.auth {
+            return self.execute_digest(http_request, request, username, password).await;
+        }
+
+        let Some(AuthConfig::OAuth2(config)) = &request.auth else {
+            return self.send(http_request, request).await;
+        };
+
+        let oauth = OAuth2Client::new(&self.client, &self.token_cache);
+        set_bearer(&mut http_request, &oauth.access_token(config).await?)?;
+        let retry = http_request.try_clone();
+
+        let result = self.send(http_request, request).await?;
+        match retry {
+            Some(mut retry) if result.status == StatusCode::UNAUTHORIZED => {
+                set_bearer(&mut retry, &oauth.renew(config).await?)?;
+                self.send(retry, request).await
+            }
+            _ => Ok(result),
+        }
+    }
+
+    /// Sends with the Digest session for this origin when there is one;
+    /// otherwise, or when the server rejects a stale nonce, answers the 401
+    /// challenge and sends once more.
+    async fn execute_digest(
+        &self,
+        mut http_request: reqwest::Request,
+        request: &RequestDefinition,
+        username: &str,
+        password: &str,
+    ) -> Result<ExecutionResult> {
+        let url = http_request.url().clone();
+        let origin = url.origin().ascii_serialization();
+        let uri = match url.query() {
+            Some(query) => format!("{}?{}", url.path(), query),
+            None => url.path().to_string(),
+        };
+        let method = http_request.method().to_string();
+        let authorize = |http_request: &mut reqwest::Request| -> Result<()> {
+            if let Some(authorization) = self.digest_sessions.authorize(&origin, username, password, &method, &uri) {
+                let value = HeaderValue::from_str(&authorization).context("Digest credentials are not a valid header value")?;
+                http_request.headers_mut().insert(AUTHORIZATION, value);
+            }
+            Ok(())
+        };
+
+        let retry = http_request.try_clone();
+        authorize(&mut http_request)?;
+        let result = self.send(http_request, request).await?;
+
+        if result.status != StatusCode::UNAUTHORIZED {
+            return Ok(result);
+        }
+        let (Some(challenge), Some(mut retry)) = (DigestChallenge::from_headers(&result.headers), retry) else {
+            return Ok(result);
+        };
+        self.digest_sessions.start(&origin, challenge);
+        authorize(&mut retry)?;
+        self.send(retry, request).await
+    }
+
+    async fn build(&self, request: &RequestDefinition) -> Result<reqwest::Request> {
+        let mut builder = self.client.request(Method::from(&request.method), &request.url);
+
+        if let Some(params) = &request.params {
+            builder = builder.query(params);
+        }
+
+        if let Some(headers) = &request.headers {
+            for (name, value) in headers {
+                builder = builder.header(name, value);
+            }
+        }
+
+        if let Some(body) = &request.body {
+            builder = match body {
+                RequestBody::Json(json) => builder.json(json),
+                RequestBody::Text(text) => builder.body(text.clone()),
+                RequestBody::Form(form) => builder.form(form),
+                RequestBody::File(path) => {
+                    let content = tokio::fs::read(path)
+                        .await
+                        .with_context(|| format!("Failed to read body file {}", path))?;
+                    builder.body(content)
+                }
+            };
+        }
+
+        if let Some(auth) = &request.auth {
+            builder = match auth {
+                AuthConfig::Bearer { token } => builder.bearer_auth(token),
+                AuthConfig::Basic { username, password } => builder.basic_auth(username, Some(password)),
+                AuthConfig::ApiKey { key, value, location } => match location {
+                    ApiKeyLocation::Header => builder.header(key, value),
+                    ApiKeyLocation::Query => builder.query(&[(key, value)]),
+                },
+                AuthConfig::Jwt(config) => {
+                    let token = mint_jwt(config, Utc::now().timestamp())?;
+                    match &config.header {
+                        Some(header) => builder.header(header, token),
+                        None => builder.bearer_auth(token),
+                    }
+                }
+                // Need the server or the final request; handled in `execute`.
+                AuthConfig::OAuth2(_)
+                | AuthConfig::AwsSigV4(_)
+                | AuthConfig::Hmac(_)
+                | AuthConfig::Digest { .. } => builder,
+            };
         }
+
+        builder
+            .build()
+            .with_context(|| format!("Failed to build request '{}' for {}", request.name, request.url))
+    }
+
+    async fn send(&self, http_request: reqwest::Request, request: &RequestDefinition) -> Result<ExecutionResult> {
+        let start = Instant::now();
+        let response = self.client
+            .execute(http_request)
+            .await
+            .with_context(|| format!("Failed to send request '{}' to {}", request.name, request.url))?;
+
+        let status = response.status();
+        let headers = response.headers().clone();
+        let url = response.url().to_string();
+        let body = response
+            .bytes()
+            .await
+            .with_context(|| format!("Failed to read response body for '{}'", request.name))?
+            .to_vec();
+        let elapsed = start.elapsed();
+
+        Ok(ExecutionResult {
+            status,
+            headers,
+            body,
+            url,
+            elapsed,
+        })
+    }
+}
+
+fn set_bearer(http_request: &mut reqwest::Request, token: &str) -> Result<()> {
+    let value = HeaderValue::from_str(&format!("Bearer {}", token)).context("Access token is not a valid header value")?;
+    http_request.headers_mut().insert(AUTHORIZATION, value);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use crate::request::{AwsSigV4Config, JwtAlgorithm, JwtConfig, OAuth2Config, OAuth2GrantType};
+    use wiremock::matchers::{
+        body_json, body_string_contains, header, header_exists, header_regex, method, path, query_param,
+    };
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn request(method: HttpMethod, url: String) -> RequestDefinition {
+        RequestDefinition {
+            name: "Test".to_string(),
+            method,
+            url,
+            headers: None,
+            params: None,
+            body: None,
+            auth: None,
+            tests: None,
+            extract: None,
+            template: None,
+            base_dir: std::path::PathBuf::new(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_execute_get_with_params_and_headers() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/users"))
+            .and(query_param("limit", "10"))
+            .and(header("Accept", "application/json"))
+            .respond_with(ResponseTemplate::new(200)
+                .insert_header("X-Test", "yes")
+                .set_body_string(r#"{"data":[{"id":1}]}"#))
+            .mount(&server)
+            .await;
+
+        let mut req = request(HttpMethod::GET, format!("{}/users", server.uri()));
+        req.params = Some(HashMap::from([("limit".to_string(), "10".to_string())]));
+        req.headers = Some(HashMap::from([("Accept".to_string(), "application/json".to_string())]));
+
+        let result = RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory()).execute(&req).await.unwrap();
+        assert_eq!(result.status, StatusCode::OK);
+        assert_eq!(result.headers.get("X-Test").unwrap(), "yes");
+        assert_eq!(result.body_json().unwrap()["data"][0]["id"], 1);
+        assert!(result.url.ends_with("/users?limit=10"));
+    }
+
+    #[tokio::test]
+    async fn test_execute_post_json_with_bearer_auth() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/users"))
+            .and(header("Authorization", "Bearer abc"))
+            .and(body_json(serde_json::json!({"name": "John"})))
+            .respond_with(ResponseTemplate::new(201).set_body_string("created"))
+            .mount(&server)
+            .await;
+
+        let mut req = request(HttpMethod::POST, format!("{}/users", server.uri()));
+        req.body = Some(RequestBody::Json(serde_json::json!({"name": "John"})));
+        req.auth = Some(AuthConfig::Bearer { token: "abc".to_string() });
+
+        let result = RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory()).execute(&req).await.unwrap();
+        assert_eq!(result.status, StatusCode::CREATED);
+        assert_eq!(result.body_text(), "created");
     }
 
-    pub async fn execute(&self, request: &RequestDefinition, environment: &EnvironmentResolver) -> Result<reqwest::Response> {
-        todo!("Implement request execution")
+    #[tokio::test]
+    async fn test_execute_api_key_in_query() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(query_param("api_key", "secret"))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&server)
+            .await;
+
+        let mut req = request(HttpMethod::GET, server.uri());
+        req.auth = Some(AuthConfig::ApiKey {
+            key: "api_key".to_string(),
+            value: "secret".to_string(),
+            location: ApiKeyLocation::Query,
+        });
+
+        let result = RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory()).execute(&req).await.unwrap();
+        assert_eq!(result.status, StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_execute_oauth2_renews_token_on_401() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains("grant_type=client_credentials"))
+            .respond_with(ResponseTemplate::new(200)
+                .set_body_string(r#"{"access_token":"stale","refresh_token":"r1","expires_in":3600}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/token"))
+            .and(body_string_contains("grant_type=refresh_token"))
+            .and(body_string_contains("refresh_token=r1"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"access_token":"fresh","expires_in":3600}"#))
+            .expect(1)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/me"))
+            .and(header("Authorization", "Bearer fresh"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/me"))
+            .respond_with(ResponseTemplate::new(401))
+            .mount(&server)
+            .await;
+
+        let mut req = request(HttpMethod::GET, format!("{}/me", server.uri()));
+        req.auth = Some(AuthConfig::OAuth2(OAuth2Config {
+            grant_type: OAuth2GrantType::ClientCredentials,
+            token_url: format!("{}/token", server.uri()),
+            client_id: "cli".to_string(),
+            client_secret: Some("secret".to_string()),
+            scopes: None,
+            audience: None,
+            username: None,
+            password: None,
+            refresh_token: None,
+        }));
+
+        let executor = RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory());
+        assert_eq!(executor.execute(&req).await.unwrap().status, StatusCode::OK);
+        // The renewed token is cached for the next request.
+        assert_eq!(executor.execute(&req).await.unwrap().status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_execute_signs_aws_sigv4_requests() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/items"))
+            .and(header_exists("x-amz-date"))
+            .and(header_regex("Authorization", r"^AWS4-HMAC-SHA256 Credential=AKID/\d{8}/eu-west-1/execute-api/aws4_request, SignedHeaders=accept;host;x-amz-date, Signature=[0-9a-f]{64}$"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let mut req = request(HttpMethod::GET, format!("{}/items", server.uri()));
+        req.headers = Some(HashMap::from([("Accept".to_string(), "application/json".to_string())]));
+        req.auth = Some(AuthConfig::AwsSigV4(AwsSigV4Config {
+            access_key: "AKID".to_string(),
+            secret_key: "secret".to_string(),
+            session_token: None,
+            region: "eu-west-1".to_string(),
+            service: "execute-api".to_string(),
+        }));
+
+        let result = RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory()).execute(&req).await.unwrap();
+        assert_eq!(result.status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_execute_sends_minted_jwt_as_bearer_or_custom_header() {
+        let server = MockServer::start().await;
+        let jwt = r"[\w-]+\.[\w-]+\.[\w-]+$";
+        Mock::given(method("GET"))
+            .and(path("/bearer"))
+            .and(header_regex("Authorization", &format!("^Bearer {}", jwt)))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/custom"))
+            .and(header_regex("X-Service-Token", &format!("^{}", jwt)))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let config = JwtConfig {
+            algorithm: JwtAlgorithm::HS256,
+            secret: Some("s3cret".to_string()),
+            key_file: None,
+            key_id: None,
+            claims: serde_json::Map::from_iter([("sub".to_string(), serde_json::json!("billing"))]),
+            iat_offset: None,
+            exp_offset: None,
+            header: None,
+        };
+        let executor = RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory());
+
+        let mut req = request(HttpMethod::GET, format!("{}/bearer", server.uri()));
+        req.auth = Some(AuthConfig::Jwt(config.clone()));
+        assert_eq!(executor.execute(&req).await.unwrap().status, StatusCode::OK);
+
+        let mut req = request(HttpMethod::GET, format!("{}/custom", server.uri()));
+        req.auth = Some(AuthConfig::Jwt(JwtConfig { header: Some("X-Service-Token".to_string()), ..config }));
+        assert_eq!(executor.execute(&req).await.unwrap().status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_execute_digest_answers_challenge_and_reuses_nonce() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/status"))
+            .and(header_regex("Authorization", r#"^Digest username="admin", realm="appliance", nonce="abc", uri="/status\?verbose=1", algorithm=MD5, response="[0-9a-f]{32}", qop=auth, nc=0000000[12], cnonce="[0-9a-f]{32}", opaque="xyz"$"#))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(2)
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/status"))
+            .respond_with(ResponseTemplate::new(401).insert_header(
+                "WWW-Authenticate",
+                r#"Digest realm="appliance", qop="auth", nonce="abc", opaque="xyz", algorithm=MD5"#,
+            ))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let mut req = request(HttpMethod::GET, format!("{}/status?verbose=1", server.uri()));
+        req.auth = Some(AuthConfig::Digest { username: "admin".to_string(), password: "pw".to_string() });
+
+        let executor = RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory());
+        assert_eq!(executor.execute(&req).await.unwrap().status, StatusCode::OK);
+        // The second request reuses the nonce without another challenge.
+        assert_eq!(executor.clone().execute(&req).await.unwrap().status, StatusCode::OK);
     }
 }
@@ -2,8 +2,10 @@ pub mod models;
 pub mod parser;
 pub mod executor;
 pub mod validator;
+pub mod auth;
 
 pub use models::*;
 pub use parser::*;
 pub use executor::*;
 pub use validator::*;
+pub use auth::*;
@@ -1,8 +1,11 @@
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 
-use crate::environment::EnvironmentResolver;
+use crate::environment::{mask_value, EnvironmentResolver};
+use crate::utils::load_and_parse_file;
 
 #[derive(Error, Debug)]
 pub enum ValidationError {
@@ -24,6 +27,22 @@ pub enum ValidationError {
     FileNotFound(String),
     #[error("Unsupported file format: {0}")]
     UnsupportedFormat(String),
+    #[error("Invalid assertion: {0}")]
+    InvalidAssertion(String),
+    #[error("Invalid workflow: {0}")]
+    InvalidWorkflow(String),
+    #[error("Environment inheritance cycle: {0}")]
+    EnvironmentCycle(String),
+    #[error("Unresolved variables: {}", .0.join(", "))]
+    UnresolvedVariables(Vec<String>),
+    #[error("Variable reference cycle: {0}")]
+    VariableCycle(String),
+    #[error("Variables nested too deeply, {0}")]
+    VariableNesting(String),
+    #[error("Template error: {0}")]
+    Template(String),
+    #[error("Secret error: {0}")]
+    Secret(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,9 +52,25 @@ pub struct RequestDefinition {
     pub url: String,
     pub headers: Option<HashMap<String, String>>,
     pub params: Option<HashMap<String, String>>,
+    #[serde(default, with = "serde_yaml::with::singleton_map")]
     pub body: Option<RequestBody>,
+    #[serde(default, with = "serde_yaml::with::singleton_map")]
     pub auth: Option<AuthConfig>,
     pub tests: Option<Vec<TestAssertion>>,
+    #[serde(alias = "capture")]
+    pub extract: Option<HashMap<String, Capture>>,
+    /// Set to `handlebars` to render the whole file as a Handlebars template
+    /// before it is parsed.
+    pub template: Option<TemplateEngine>,
+    /// Directory schema file paths are relative to; the request file's directory.
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum TemplateEngine {
+    Handlebars,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
@@ -50,7 +85,7 @@ pub enum HttpMethod {
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(untagged)]
+#[serde(rename_all = "lowercase")]
 pub enum RequestBody {
     Json(serde_json::Value),
     Text(String),
@@ -63,6 +98,114 @@ pub enum AuthConfig {
     Bearer { token: String },
     Basic { username: String, password: String },
     ApiKey { key: String, value: String, location: ApiKeyLocation },
+    /// Fetches an access token from an OAuth2 token endpoint and sends it as
+    /// a Bearer token.
+    OAuth2(OAuth2Config),
+    /// Signs the final request with AWS Signature Version 4.
+    AwsSigV4(AwsSigV4Config),
+    /// HTTP Digest authentication; the challenge is answered automatically.
+    Digest { username: String, password: String },
+    /// Signs a canonical string built from the final request with an HMAC.
+    Hmac(HmacConfig),
+    /// A JWT minted from `claims` for every request.
+    Jwt(JwtConfig),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct HmacConfig {
+    pub algorithm: HmacAlgorithm,
+    pub key: String,
+    /// Template for the string to sign, e.g. `"{method}\n{path}\n{timestamp}\n{body_hash}"`
+    /// (the default). See `sign_hmac` for the placeholders.
+    pub canonical: Option<String>,
+    /// `hex` (default) or `base64`.
+    pub encoding: Option<SignatureEncoding>,
+    /// Header the signature goes in; `X-Signature` by default.
+    pub header: Option<String>,
+    /// Text put before the signature in the header, e.g. `sha256=`.
+    pub prefix: Option<String>,
+    /// Header that carries the timestamp that was signed, if any.
+    pub timestamp_header: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum HmacAlgorithm {
+    #[serde(rename = "sha1", alias = "sha-1", alias = "SHA-1", alias = "SHA1")]
+    Sha1,
+    #[serde(rename = "sha256", alias = "sha-256", alias = "SHA-256", alias = "SHA256")]
+    Sha256,
+    #[serde(rename = "sha512", alias = "sha-512", alias = "SHA-512", alias = "SHA512")]
+    Sha512,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct JwtConfig {
+    pub algorithm: JwtAlgorithm,
+    /// Shared secret for HS256.
+    pub secret: Option<String>,
+    /// PEM private key for RS256 and ES256.
+    pub key_file: Option<String>,
+    /// Sent as the `kid` header.
+    pub key_id: Option<String>,
+    /// Claims to sign; string values are templated.
+    #[serde(default)]
+    pub claims: serde_json::Map<String, serde_json::Value>,
+    /// Seconds added to the signing time for `iat`; 0 by default.
+    pub iat_offset: Option<i64>,
+    /// Seconds added to the signing time for `exp`; 300 by default.
+    pub exp_offset: Option<i64>,
+    /// Header that carries the bare token; without one it is sent as
+    /// `Authorization: Bearer <token>`.
+    pub header: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[allow(clippy::upper_case_acronyms)]
+pub enum JwtAlgorithm {
+    HS256,
+    RS256,
+    ES256,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum SignatureEncoding {
+    #[default]
+    Hex,
+    Base64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AwsSigV4Config {
+    pub access_key: String,
+    pub secret_key: String,
+    pub session_token: Option<String>,
+    pub region: String,
+    /// Signing name of the service, e.g. `execute-api` for API Gateway.
+    pub service: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OAuth2Config {
+    pub grant_type: OAuth2GrantType,
+    pub token_url: String,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    pub scopes: Option<Vec<String>>,
+    pub audience: Option<String>,
+    /// Required by the `password` grant.
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Required by the `refresh_token` grant.
+    pub refresh_token: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum OAuth2GrantType {
+    ClientCredentials,
+    Password,
+    RefreshToken,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -71,13 +214,154 @@ pub enum ApiKeyLocation {
     Query,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TestAssertion {
     pub status_code: Option<u16>,
     pub response_time_less_than: Option<u64>,
     pub json_path: Option<String>,
+    pub header: Option<String>,
+    pub cookie: Option<String>,
+    pub body: Option<BodyTarget>,
+    pub schema: Option<SchemaSource>,
     pub exists: Option<bool>,
     pub equals: Option<serde_json::Value>,
+    pub not_equals: Option<serde_json::Value>,
+    pub contains: Option<serde_json::Value>,
+    pub matches: Option<String>,
+    pub gt: Option<f64>,
+    pub gte: Option<f64>,
+    pub lt: Option<f64>,
+    pub lte: Option<f64>,
+    #[serde(rename = "type")]
+    pub value_type: Option<JsonType>,
+    pub length: Option<usize>,
+    pub one_of: Option<Vec<serde_json::Value>>,
+    pub is_empty: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum JsonType {
+    String,
+    Number,
+    Boolean,
+    Array,
+    Object,
+    Null,
+}
+
+impl JsonType {
+    pub fn of(value: &serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::String(_) => JsonType::String,
+            serde_json::Value::Number(_) => JsonType::Number,
+            serde_json::Value::Bool(_) => JsonType::Boolean,
+            serde_json::Value::Array(_) => JsonType::Array,
+            serde_json::Value::Object(_) => JsonType::Object,
+            serde_json::Value::Null => JsonType::Null,
+        }
+    }
+}
+
+impl std::fmt::Display for JsonType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            JsonType::String => "string",
+            JsonType::Number => "number",
+            JsonType::Boolean => "boolean",
+            JsonType::Array => "array",
+            JsonType::Object => "object",
+            JsonType::Null => "null",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// What a `body` assertion looks at: the raw text or its size in bytes.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum BodyTarget {
+    Text,
+    Size,
+}
+
+/// Where to take a captured variable from. Exactly one of `json_path`,
+/// `header` or `regex` is set; `group` picks the regex capture group.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct Capture {
+    pub json_path: Option<String>,
+    pub header: Option<String>,
+    pub regex: Option<String>,
+    pub group: Option<usize>,
+}
+
+/// A JSON Schema given either as a path to a schema file, relative to the
+/// request file, or inline.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum SchemaSource {
+    File(String),
+    Inline(serde_json::Value),
+}
+
+impl SchemaSource {
+    /// Loads and compiles the schema. A file path is relative to `base_dir`;
+    /// relative `$ref`s resolve against the schema file, or against
+    /// `base_dir` for an inline schema.
+    pub fn validator(&self, base_dir: &Path) -> Result<jsonschema::Validator, ValidationError> {
+        let (schema, base_uri) = match self {
+            SchemaSource::File(path) => {
+                let path = base_dir.join(path);
+                let base_uri = std::path::absolute(&path).ok()
+                    .and_then(|path| reqwest::Url::from_file_path(path).ok());
+                (load_and_parse_file(&path)?, base_uri)
+            }
+            SchemaSource::Inline(schema) => {
+                let base_uri = std::path::absolute(base_dir).or_else(|_| std::env::current_dir()).ok()
+                    .and_then(|dir| reqwest::Url::from_directory_path(dir).ok());
+                (schema.clone(), base_uri)
+            }
+        };
+        let base_uri = base_uri.ok_or_else(|| {
+            ValidationError::InvalidAssertion(format!("Cannot resolve schema references from {}", base_dir.display()))
+        })?;
+
+        jsonschema::options()
+            .with_base_uri(base_uri.as_str())
+            .build(&schema)
+            .map_err(|error| ValidationError::InvalidAssertion(format!("Invalid JSON Schema: {}", error)))
+    }
+}
+
+impl TestAssertion {
+    /// Number of response parts (`json_path`, `header`, `cookie`, `body`) this assertion targets.
+    pub fn target_count(&self) -> usize {
+        [
+            self.json_path.is_some(),
+            self.header.is_some(),
+            self.cookie.is_some(),
+            self.body.is_some(),
+        ]
+        .iter()
+        .filter(|set| **set)
+        .count()
+    }
+
+    /// True when any matcher that compares the selected value is set.
+    pub fn has_value_matchers(&self) -> bool {
+        self.equals.is_some()
+            || self.not_equals.is_some()
+            || self.contains.is_some()
+            || self.matches.is_some()
+            || self.gt.is_some()
+            || self.gte.is_some()
+            || self.lt.is_some()
+            || self.lte.is_some()
+            || self.value_type.is_some()
+            || self.length.is_some()
+            || self.one_of.is_some()
+            || self.is_empty.is_some()
+    }
 }
 
 impl RequestDefinition {
@@ -104,6 +388,32 @@ impl RequestDefinition {
             }
         }
 
+        if let Some(AuthConfig::OAuth2(config)) = &self.auth {
+            if config.token_url.trim().is_empty() {
+                return Err(ValidationError::MissingField("auth.token_url".to_string()));
+            }
+            let required: &[(&str, &Option<String>)] = match config.grant_type {
+                OAuth2GrantType::ClientCredentials => &[],
+                OAuth2GrantType::Password => &[("username", &config.username), ("password", &config.password)],
+                OAuth2GrantType::RefreshToken => &[("refresh_token", &config.refresh_token)],
+            };
+            for (field, value) in required {
+                if value.is_none() {
+                    return Err(ValidationError::MissingField(format!("auth.{}", field)));
+                }
+            }
+        }
+
+        if let Some(AuthConfig::Jwt(config)) = &self.auth {
+            let (field, value) = match config.algorithm {
+                JwtAlgorithm::HS256 => ("secret", &config.secret),
+                JwtAlgorithm::RS256 | JwtAlgorithm::ES256 => ("key_file", &config.key_file),
+            };
+            if value.is_none() {
+                return Err(ValidationError::MissingField(format!("auth.{}", field)));
+            }
+        }
+
         if let Some(tests) = &self.tests {
             for test in tests {
                 if let Some(status_code) = test.status_code {
@@ -113,6 +423,76 @@ impl RequestDefinition {
                         ));
                     }
                 }
+
+                if let Some(path) = &test.json_path {
+                    if let Err(error) = serde_json_path::JsonPath::parse(path) {
+                        return Err(ValidationError::InvalidAssertion(
+                            format!("Invalid JSONPath '{}': {}", path, error)
+                        ));
+                    }
+                }
+
+                if test.target_count() > 1 {
+                    return Err(ValidationError::InvalidAssertion(
+                        "Only one of json_path, header, cookie or body can be set per assertion".to_string()
+                    ));
+                }
+
+                if (test.has_value_matchers() || test.exists.is_some()) && test.target_count() == 0 {
+                    return Err(ValidationError::InvalidAssertion(
+                        "Value matchers require a json_path, header, cookie or body".to_string()
+                    ));
+                }
+
+                if let Some(pattern) = &test.matches {
+                    if let Err(error) = regex::Regex::new(pattern) {
+                        return Err(ValidationError::InvalidAssertion(
+                            format!("Invalid regex '{}': {}", pattern, error)
+                        ));
+                    }
+                }
+
+                if let Some(schema) = &test.schema {
+                    schema.validator(&self.base_dir)?;
+                }
+
+                if let Some(one_of) = &test.one_of {
+                    if one_of.is_empty() {
+                        return Err(ValidationError::InvalidAssertion(
+                            "one_of needs at least one value".to_string()
+                        ));
+                    }
+                }
+            }
+        }
+
+        if let Some(extract) = &self.extract {
+            for (name, capture) in extract {
+                let sources = [capture.json_path.is_some(), capture.header.is_some(), capture.regex.is_some()]
+                    .iter()
+                    .filter(|set| **set)
+                    .count();
+                if sources != 1 {
+                    return Err(ValidationError::InvalidAssertion(
+                        format!("Capture '{}' needs exactly one of json_path, header or regex", name)
+                    ));
+                }
+
+                if let Some(path) = &capture.json_path {
+                    if let Err(error) = serde_json_path::JsonPath::parse(path) {
+                        return Err(ValidationError::InvalidAssertion(
+                            format!("Invalid JSONPath '{}' in capture '{}': {}", path, name, error)
+                        ));
+                    }
+                }
+
+                if let Some(pattern) = &capture.regex {
+                    if let Err(error) = regex::Regex::new(pattern) {
+                        return Err(ValidationError::InvalidAssertion(
+                            format!("Invalid regex '{}' in capture '{}': {}", pattern, name, error)
+                        ));
+                    }
+                }
             }
         }
 
@@ -124,7 +504,396 @@ impl RequestDefinition {
         !name.trim().is_empty() && name.chars().all(|c| c.is_ascii() && !c.is_control())
     }
 
+    /// Substitutes `{{variables}}` in every templated field: url, headers,
+    /// params, body (including JSON object keys) and auth.
+    ///
+    /// When `env_resolver` is strict, every placeholder left unresolved is
+    /// collected together with the field it appeared in and reported as a
+    /// single `UnresolvedVariables` error. A cycle between nested variables
+    /// is always an error.
     pub fn resolve_with_env(&self, env_resolver: &EnvironmentResolver) -> Result<RequestDefinition, ValidationError> {
-        Ok(self.clone())
+        let unresolved = RefCell::new(Vec::new());
+        let error = RefCell::new(None);
+        let resolve = |field: &str, template: &str| {
+            match env_resolver.resolve_template_checked(template) {
+                Ok((resolved, missing)) => {
+                    unresolved.borrow_mut().extend(missing.into_iter().map(|name| format!("{} in {}", name, field)));
+                    resolved
+                }
+                Err(e) => {
+                    error.borrow_mut().get_or_insert(e);
+                    template.to_string()
+                }
+            }
+        };
+        let resolve_map = |prefix: &str, map: &HashMap<String, String>| {
+            map.iter()
+                .map(|(key, value)| {
+                    let field = format!("{}.{}", prefix, key);
+                    (resolve(&field, key), resolve(&field, value))
+                })
+                .collect::<HashMap<_, _>>()
+        };
+
+        let mut resolved = self.clone();
+        resolved.url = resolve("url", &self.url);
+        resolved.headers = self.headers.as_ref().map(|headers| resolve_map("headers", headers));
+        resolved.params = self.params.as_ref().map(|params| resolve_map("params", params));
+
+        resolved.body = self.body.as_ref().map(|body| match body {
+            RequestBody::Json(json) => RequestBody::Json(Self::resolve_json("body", json, &resolve)),
+            RequestBody::Text(text) => RequestBody::Text(resolve("body", text)),
+            RequestBody::Form(form) => RequestBody::Form(resolve_map("body", form)),
+            RequestBody::File(path) => RequestBody::File(resolve("body.file", path)),
+        });
+
+        resolved.auth = self.auth.as_ref().map(|auth| match auth {
+            AuthConfig::Bearer { token } => AuthConfig::Bearer { token: resolve("auth.token", token) },
+            AuthConfig::Basic { username, password } => AuthConfig::Basic {
+                username: resolve("auth.username", username),
+                password: resolve("auth.password", password),
+            },
+            AuthConfig::ApiKey { key, value, location } => AuthConfig::ApiKey {
+                key: resolve("auth.key", key),
+                value: resolve("auth.value", value),
+                location: location.clone(),
+            },
+            AuthConfig::OAuth2(config) => {
+                let resolve_option = |field: &str, value: &Option<String>| {
+                    value.as_ref().map(|value| resolve(&format!("auth.{}", field), value))
+                };
+                AuthConfig::OAuth2(OAuth2Config {
+                    grant_type: config.grant_type,
+                    token_url: resolve("auth.token_url", &config.token_url),
+                    client_id: resolve("auth.client_id", &config.client_id),
+                    client_secret: resolve_option("client_secret", &config.client_secret),
+                    scopes: config.scopes.as_ref().map(|scopes| {
+                        scopes.iter().map(|scope| resolve("auth.scopes", scope)).collect()
+                    }),
+                    audience: resolve_option("audience", &config.audience),
+                    username: resolve_option("username", &config.username),
+                    password: resolve_option("password", &config.password),
+                    refresh_token: resolve_option("refresh_token", &config.refresh_token),
+                })
+            }
+            AuthConfig::Hmac(config) => AuthConfig::Hmac(HmacConfig {
+                key: resolve("auth.key", &config.key),
+                canonical: config.canonical.as_ref().map(|canonical| resolve("auth.canonical", canonical)),
+                header: config.header.as_ref().map(|header| resolve("auth.header", header)),
+                prefix: config.prefix.as_ref().map(|prefix| resolve("auth.prefix", prefix)),
+                timestamp_header: config.timestamp_header.as_ref().map(|header| resolve("auth.timestamp_header", header)),
+                ..config.clone()
+            }),
+            AuthConfig::Jwt(config) => AuthConfig::Jwt(JwtConfig {
+                secret: config.secret.as_ref().map(|secret| resolve("auth.secret", secret)),
+                key_file: config.key_file.as_ref().map(|path| resolve("auth.key_file", path)),
+                key_id: config.key_id.as_ref().map(|key_id| resolve("auth.key_id", key_id)),
+                claims: config.claims.iter()
+                    .map(|(name, value)| {
+                        let field = format!("auth.claims.{}", name);
+                        (name.clone(), Self::resolve_json(&field, value, &resolve))
+                    })
+                    .collect(),
+                header: config.header.as_ref().map(|header| resolve("auth.header", header)),
+                ..config.clone()
+            }),
+            AuthConfig::Digest { username, password } => AuthConfig::Digest {
+                username: resolve("auth.username", username),
+                password: resolve("auth.password", password),
+            },
+            AuthConfig::AwsSigV4(config) => AuthConfig::AwsSigV4(AwsSigV4Config {
+                access_key: resolve("auth.access_key", &config.access_key),
+                secret_key: resolve("auth.secret_key", &config.secret_key),
+                session_token: config.session_token.as_ref().map(|token| resolve("auth.session_token", token)),
+                region: resolve("auth.region", &config.region),
+                service: resolve("auth.service", &config.service),
+            }),
+        });
+
+        if let Some(error) = error.into_inner() {
+            return Err(error);
+        }
+
+        let mut unresolved = unresolved.into_inner();
+        if env_resolver.is_strict() && !unresolved.is_empty() {
+            unresolved.sort();
+            unresolved.dedup();
+            return Err(ValidationError::UnresolvedVariables(unresolved));
+        }
+
+        Ok(resolved)
+    }
+
+    /// A copy that is safe to print: auth credentials, and headers, params
+    /// and body fields whose names look sensitive, are masked with `mask_value`.
+    pub fn masked(&self) -> RequestDefinition {
+        let mask_map = |map: &HashMap<String, String>| {
+            map.iter()
+                .map(|(name, value)| (name.clone(), mask_value(name, value)))
+                .collect::<HashMap<_, _>>()
+        };
+        let mask = |name: &str, value: &String| mask_value(name, value);
+        let mask_option = |name: &str, value: &Option<String>| value.as_ref().map(|value| mask_value(name, value));
+
+        let mut masked = self.clone();
+        masked.headers = self.headers.as_ref().map(mask_map);
+        masked.params = self.params.as_ref().map(mask_map);
+        masked.body = self.body.as_ref().map(|body| match body {
+            RequestBody::Json(json) => RequestBody::Json(Self::mask_json(json)),
+            RequestBody::Form(form) => RequestBody::Form(mask_map(form)),
+            other => other.clone(),
+        });
+        masked.auth = self.auth.as_ref().map(|auth| match auth {
+            AuthConfig::Bearer { token } => AuthConfig::Bearer { token: mask("token", token) },
+            AuthConfig::Basic { username, password } => AuthConfig::Basic {
+                username: username.clone(),
+                password: mask("password", password),
+            },
+            AuthConfig::ApiKey { key, value, location } => AuthConfig::ApiKey {
+                key: key.clone(),
+                value: mask("api_key", value),
+                location: location.clone(),
+            },
+            AuthConfig::OAuth2(config) => AuthConfig::OAuth2(OAuth2Config {
+                client_secret: mask_option("client_secret", &config.client_secret),
+                password: mask_option("password", &config.password),
+                refresh_token: mask_option("refresh_token", &config.refresh_token),
+                ..config.clone()
+            }),
+            AuthConfig::AwsSigV4(config) => AuthConfig::AwsSigV4(AwsSigV4Config {
+                secret_key: mask("secret_key", &config.secret_key),
+                session_token: mask_option("session_token", &config.session_token),
+                ..config.clone()
+            }),
+            AuthConfig::Digest { username, password } => AuthConfig::Digest {
+                username: username.clone(),
+                password: mask("password", password),
+            },
+            AuthConfig::Hmac(config) => AuthConfig::Hmac(HmacConfig {
+                key: mask("secret", &config.key),
+                ..config.clone()
+            }),
+            AuthConfig::Jwt(config) => AuthConfig::Jwt(JwtConfig {
+                secret: mask_option("secret", &config.secret),
+                ..config.clone()
+            }),
+        });
+        masked
+    }
+
+    fn mask_json(json: &serde_json::Value) -> serde_json::Value {
+        match json {
+            serde_json::Value::Array(items) => serde_json::Value::Array(items.iter().map(Self::mask_json).collect()),
+            serde_json::Value::Object(map) => serde_json::Value::Object(
+                map.iter()
+                    .map(|(key, value)| match value {
+                        serde_json::Value::String(text) => (key.clone(), serde_json::Value::String(mask_value(key, text))),
+                        other => (key.clone(), Self::mask_json(other)),
+                    })
+                    .collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+
+    /// Resolves every string and object key inside `json`; numbers, booleans
+    /// and nulls are left untouched. `field` is the path used in error reports.
+    fn resolve_json(
+        field: &str,
+        json: &serde_json::Value,
+        resolve: &impl Fn(&str, &str) -> String,
+    ) -> serde_json::Value {
+        match json {
+            serde_json::Value::String(text) => serde_json::Value::String(resolve(field, text)),
+            serde_json::Value::Array(items) => serde_json::Value::Array(
+                items.iter()
+                    .enumerate()
+                    .map(|(index, item)| Self::resolve_json(&format!("{}[{}]", field, index), item, resolve))
+                    .collect(),
+            ),
+            serde_json::Value::Object(map) => serde_json::Value::Object(
+                map.iter()
+                    .map(|(key, value)| {
+                        let field = format!("{}.{}", field, key);
+                        (resolve(&field, key), Self::resolve_json(&field, value, resolve))
+                    })
+                    .collect(),
+            ),
+            other => other.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn resolver() -> EnvironmentResolver {
+        let mut resolver = EnvironmentResolver::default();
+        resolver.set_variable("base_url", "https://api.example.com");
+        resolver.set_variable("token", "abc");
+        resolver.set_variable("field", "email");
+        resolver.set_variable("user", "john");
+        resolver
+    }
+
+    fn request() -> RequestDefinition {
+        RequestDefinition {
+            name: "Resolve".to_string(),
+            method: HttpMethod::POST,
+            url: "{{base_url}}/users".to_string(),
+            headers: Some(HashMap::from([("X-{{user}}".to_string(), "Bearer {{token}}".to_string())])),
+            params: Some(HashMap::from([("q".to_string(), "{{user}}".to_string())])),
+            body: None,
+            auth: None,
+            tests: None,
+            extract: None,
+            template: None,
+            base_dir: PathBuf::new(),
+        }
+    }
+
+    #[test]
+    fn test_resolve_with_env_url_headers_and_params() {
+        let resolved = request().resolve_with_env(&resolver()).unwrap();
+        assert_eq!(resolved.url, "https://api.example.com/users");
+        assert_eq!(resolved.headers.unwrap().get("X-john"), Some(&"Bearer abc".to_string()));
+        assert_eq!(resolved.params.unwrap().get("q"), Some(&"john".to_string()));
+    }
+
+    #[test]
+    fn test_resolve_with_env_json_body_keeps_non_strings() {
+        let mut request = request();
+        request.body = Some(RequestBody::Json(json!({
+            "{{field}}": "{{user}}@example.com",
+            "tags": ["{{user}}", 1, true, null],
+            "nested": {"count": 3, "token": "{{token}}"}
+        })));
+
+        let resolved = request.resolve_with_env(&resolver()).unwrap();
+        match resolved.body {
+            Some(RequestBody::Json(json)) => assert_eq!(json, json!({
+                "email": "john@example.com",
+                "tags": ["john", 1, true, null],
+                "nested": {"count": 3, "token": "abc"}
+            })),
+            other => panic!("Expected JSON body, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resolve_with_env_form_body_and_auth() {
+        let mut request = request();
+        request.body = Some(RequestBody::Form(HashMap::from([("{{field}}".to_string(), "{{user}}".to_string())])));
+        request.auth = Some(AuthConfig::ApiKey {
+            key: "X-{{user}}-Key".to_string(),
+            value: "{{token}}".to_string(),
+            location: ApiKeyLocation::Header,
+        });
+
+        let resolved = request.resolve_with_env(&resolver()).unwrap();
+        match resolved.body {
+            Some(RequestBody::Form(form)) => assert_eq!(form.get("email"), Some(&"john".to_string())),
+            other => panic!("Expected form body, got {:?}", other),
+        }
+        match resolved.auth {
+            Some(AuthConfig::ApiKey { key, value, .. }) => {
+                assert_eq!(key, "X-john-Key");
+                assert_eq!(value, "abc");
+            }
+            other => panic!("Expected API key auth, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_resolve_with_env_strict_reports_every_missing_variable() {
+        let mut request = request();
+        request.headers = Some(HashMap::from([("Authorization".to_string(), "Bearer {{auth_token}}".to_string())]));
+        request.body = Some(RequestBody::Json(json!({"user": {"id": "{{user_id}}"}, "tags": ["{{tag}}"]})));
+
+        let mut resolver = resolver();
+        assert!(request.resolve_with_env(&resolver).is_ok());
+
+        resolver.set_strict(true);
+        match request.resolve_with_env(&resolver) {
+            Err(ValidationError::UnresolvedVariables(missing)) => assert_eq!(missing, vec![
+                "{{auth_token}} in headers.Authorization".to_string(),
+                "{{tag}} in body.tags[0]".to_string(),
+                "{{user_id}} in body.user.id".to_string(),
+            ]),
+            other => panic!("Expected unresolved variables error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_masked_hides_credentials_and_sensitive_fields() {
+        let mut request = request();
+        request.headers = Some(HashMap::from([
+            ("Authorization".to_string(), "Bearer abc".to_string()),
+            ("Accept".to_string(), "application/json".to_string()),
+        ]));
+        request.body = Some(RequestBody::Json(json!({"user": {"name": "ann", "password": "pw"}})));
+        request.auth = Some(AuthConfig::AwsSigV4(AwsSigV4Config {
+            access_key: "AKID".to_string(),
+            secret_key: "wJalr".to_string(),
+            session_token: Some("session".to_string()),
+            region: "eu-west-1".to_string(),
+            service: "s3".to_string(),
+        }));
+
+        let masked = request.masked();
+        let headers = masked.headers.unwrap();
+        assert_eq!(headers["Authorization"], "********");
+        assert_eq!(headers["Accept"], "application/json");
+        match masked.body {
+            Some(RequestBody::Json(json)) => assert_eq!(json, json!({"user": {"name": "ann", "password": "********"}})),
+            other => panic!("Expected JSON body, got {:?}", other),
+        }
+        match masked.auth {
+            Some(AuthConfig::AwsSigV4(config)) => {
+                assert_eq!(config.access_key, "AKID");
+                assert_eq!(config.secret_key, "********");
+                assert_eq!(config.session_token.as_deref(), Some("********"));
+            }
+            other => panic!("Expected AWS auth, got {:?}", other),
+        }
+        assert!(!format!("{:?}", RequestDefinition {
+            auth: Some(AuthConfig::Bearer { token: "abc".to_string() }),
+            ..request
+        }.masked()).contains("abc"));
+    }
+
+    #[test]
+    fn test_schema_files_resolve_against_the_request_directory() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let schemas = temp_dir.path().join("schemas");
+        std::fs::create_dir(&schemas).unwrap();
+        std::fs::write(schemas.join("defs.json"), json!({"definitions": {"id": {"type": "integer"}}}).to_string()).unwrap();
+        std::fs::write(schemas.join("user.json"), json!({
+            "type": "object",
+            "properties": {"id": {"$ref": "defs.json#/definitions/id"}},
+            "required": ["id"]
+        }).to_string()).unwrap();
+
+        let validator = SchemaSource::File("schemas/user.json".to_string()).validator(temp_dir.path()).unwrap();
+        assert!(validator.is_valid(&json!({"id": 7})));
+        assert!(!validator.is_valid(&json!({"id": "7"})));
+
+        let inline = SchemaSource::Inline(json!({"$ref": "schemas/defs.json#/definitions/id"}));
+        assert!(inline.validator(temp_dir.path()).unwrap().is_valid(&json!(7)));
+
+        let with_schema = |schema: &str| RequestDefinition {
+            tests: Some(vec![TestAssertion {
+                schema: Some(SchemaSource::File(schema.to_string())),
+                ..Default::default()
+            }]),
+            base_dir: temp_dir.path().to_path_buf(),
+            ..request()
+        };
+        assert!(with_schema("schemas/user.json").validate().is_ok());
+        assert!(with_schema("schemas/missing.json").validate().is_err());
+        std::fs::write(schemas.join("broken.json"), json!({"type": 12}).to_string()).unwrap();
+        assert!(matches!(with_schema("schemas/broken.json").validate(), Err(ValidationError::InvalidAssertion(_))));
     }
 }
@@ -1,43 +1,98 @@
 use walkdir::WalkDir;
 use std::path::{Path, PathBuf};
 
-use crate::utils::load_and_parse_file;
+use crate::environment::{EnvironmentResolver, TemplateRenderer};
+use crate::utils::{parse_file_content, read_file};
 
 use super::{RequestDefinition, ValidationError};
 
 pub struct RequestParser;
 
 impl RequestParser {
+   /// Parses the request at `path` as written. Handlebars files are not
+   /// rendered; use `parse_file_with_env` for those.
    pub fn parse_file<P: AsRef<Path>>(path: P) -> Result<RequestDefinition, ValidationError> {
-        load_and_parse_file(path)
-   } 
+        let path = path.as_ref();
+        Self::parse_content(path, &read_file(path)?)
+   }
+
+   /// Parses the request at `path`, first rendering it against `env_resolver`
+   /// when the file opts in to Handlebars templating.
+   pub fn parse_file_with_env<P: AsRef<Path>>(
+        path: P,
+        env_resolver: &EnvironmentResolver,
+    ) -> Result<RequestDefinition, ValidationError> {
+        let path = path.as_ref();
+        let content = read_file(path)?;
+        if TemplateRenderer::is_handlebars(&content) {
+            let rendered = TemplateRenderer::render(&content, env_resolver)?;
+            return Self::parse_content(path, &rendered);
+        }
+        Self::parse_content(path, &content)
+   }
+
+   fn parse_content(path: &Path, content: &str) -> Result<RequestDefinition, ValidationError> {
+        let mut request: RequestDefinition = parse_file_content(path, content)?;
+        request.base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(request)
+   }
 
    pub fn parse_directory<P: AsRef<Path>>(
         dir: P,
     ) -> Vec<(PathBuf, Result<RequestDefinition, ValidationError>)> {
-        let mut results = Vec::new();
+        Self::find_files(dir)
+            .into_iter()
+            .map(|path| {
+                let result = Self::parse_file(&path);
+                (path, result)
+            })
+            .collect()
+    }
+
+   /// Lists the request files (`.yaml`, `.yml`, `.json`) under `dir`,
+   /// skipping workflows, environments and other data files that live
+   /// alongside them (see `is_request_file`).
+   pub fn find_files<P: AsRef<Path>>(dir: P) -> Vec<PathBuf> {
+        let mut files = Vec::new();
 
         for entry in WalkDir::new(dir).into_iter().filter_map(|e| e.ok()) {
             if entry.file_type().is_file() {
                 if let Some(ext) = entry.path().extension().and_then(|s| s.to_str()) {
-                    if matches!(ext, "yaml" | "yml" | "json") {
-                        let path_buf = entry.path().to_path_buf();
-                        let result = Self::parse_file(&path_buf);
-                        results.push((path_buf, result));
+                    if matches!(ext, "yaml" | "yml" | "json") && Self::is_request_file(entry.path()) {
+                        files.push(entry.path().to_path_buf());
                     }
                 }
             }
         }
-        results
+        files
+    }
+
+   /// True when the file at `path` has the shape of a request: a mapping
+   /// with a `method` or `url` and no workflow `steps`. Handlebars files
+   /// only take shape once rendered, and files that don't parse at all are
+   /// kept so their errors get reported.
+   pub fn is_request_file<P: AsRef<Path>>(path: P) -> bool {
+        let path = path.as_ref();
+        let Ok(content) = read_file(path) else { return true };
+        if TemplateRenderer::is_handlebars(&content) {
+            return true;
+        }
+        match parse_file_content::<serde_yaml::Value, _>(path, &content) {
+            Ok(serde_yaml::Value::Mapping(mapping)) => {
+                (mapping.contains_key("method") || mapping.contains_key("url")) && !mapping.contains_key("steps")
+            }
+            Ok(_) => false,
+            Err(_) => true,
+        }
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::request::HttpMethod; // For RequestDefinition fields
+    use crate::request::{HttpMethod, RequestBody}; // For RequestDefinition fields
     use std::io::Write;
-    use tempfile::{Builder as TempFileBuilder, NamedTempFile};
+    use tempfile::Builder as TempFileBuilder;
 
     // Test for parse_file (which now uses the utility)
     #[test]
@@ -47,7 +102,7 @@ name: "Test Request via Parser"
 method: GET
 url: "http://example.com/yaml"
 "#;
-        let mut temp_file = TempFileBuilder::new().suffix(".yaml").NamedTempFile().unwrap();
+        let mut temp_file = TempFileBuilder::new().suffix(".yaml").tempfile().unwrap();
         write!(temp_file, "{}", yaml_content).unwrap();
         temp_file.flush().unwrap();
 
@@ -67,7 +122,7 @@ url: "http://example.com/yaml"
     "url": "http://example.com/json"
 }
 "#;
-        let mut temp_file = TempFileBuilder::new().suffix(".json").NamedTempFile().unwrap();
+        let mut temp_file = TempFileBuilder::new().suffix(".json").tempfile().unwrap();
         write!(temp_file, "{}", json_content).unwrap();
         temp_file.flush().unwrap();
 
@@ -78,6 +133,133 @@ url: "http://example.com/yaml"
         assert_eq!(req_def.method, HttpMethod::POST);
     }
 
+    #[test]
+    fn test_find_files_skips_workflows_and_environments() {
+        let temp_dir = TempFileBuilder::new().prefix("test_find_files_").tempdir().unwrap();
+        let dir = temp_dir.path();
+        let files = [
+            ("get-user.yaml", "name: Get\nmethod: GET\nurl: http://example.com"),
+            ("broken.yaml", "name: [unclosed"),
+            ("no-method.json", r#"{"name": "Missing method", "url": "http://example.com"}"#),
+            ("flow.yaml", "name: Flow\nsteps:\n  - name: get\n    request: get-user.yaml"),
+            ("dev.yaml", "name: dev\nvariables:\n  token: !secret abc"),
+            ("user.schema.json", r#"{"$schema": "http://json-schema.org/draft-07/schema#", "type": "object"}"#),
+        ];
+        for (name, content) in files {
+            std::fs::write(dir.join(name), content).unwrap();
+        }
+
+        let mut found: Vec<_> = RequestParser::find_files(dir).into_iter()
+            .map(|path| path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        found.sort();
+        assert_eq!(found, vec!["broken.yaml", "get-user.yaml", "no-method.json"]);
+    }
+
+    #[test]
+    fn test_parse_handlebars_file_with_and_without_env() {
+        let content = "template: handlebars\nname: Grant\nmethod: POST\nurl: \"{{base_url}}/users/{{user}}\"\nbody:\n  json:\n    note: \"{{note}}\"\n";
+        let mut temp_file = TempFileBuilder::new().suffix(".yaml").tempfile().unwrap();
+        write!(temp_file, "{}", content).unwrap();
+        temp_file.flush().unwrap();
+
+        let raw = RequestParser::parse_file(temp_file.path()).unwrap();
+        assert_eq!(raw.url, "{{base_url}}/users/{{user}}");
+        assert_eq!(raw.base_dir, temp_file.path().parent().unwrap());
+
+        let mut env = EnvironmentResolver::default();
+        env.set_variable("user", "42");
+        env.set_variable("note", "line \"one\"\nline two");
+        let rendered = RequestParser::parse_file_with_env(temp_file.path(), &env).unwrap();
+        assert_eq!(rendered.url, "{{base_url}}/users/42");
+        match rendered.body {
+            Some(RequestBody::Json(body)) => assert_eq!(body["note"], "line \"one\"\nline two"),
+            other => panic!("Expected a JSON body, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_file_oauth2_auth() {
+        let yaml_content = r#"
+name: "OAuth Request"
+method: GET
+url: "http://example.com/me"
+auth:
+  OAuth2:
+    grant_type: client_credentials
+    token_url: "http://example.com/token"
+    client_id: "cli"
+    client_secret: "{{client_secret}}"
+    scopes: [read, write]
+"#;
+        let mut temp_file = TempFileBuilder::new().suffix(".yaml").tempfile().unwrap();
+        write!(temp_file, "{}", yaml_content).unwrap();
+        temp_file.flush().unwrap();
+
+        let req_def = RequestParser::parse_file(temp_file.path()).unwrap();
+        match req_def.auth {
+            Some(crate::request::AuthConfig::OAuth2(config)) => {
+                assert_eq!(config.grant_type, crate::request::OAuth2GrantType::ClientCredentials);
+                assert_eq!(config.scopes, Some(vec!["read".to_string(), "write".to_string()]));
+            }
+            other => panic!("Expected OAuth2 auth, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_file_hmac_auth() {
+        let yaml_content = r#"
+name: "Signed Webhook"
+method: POST
+url: "http://example.com/hooks"
+auth:
+  Hmac:
+    algorithm: SHA-256
+    key: "{{webhook_key}}"
+    canonical: "{{partner_id}}:{method}:{path}:{timestamp}:{body_hash}"
+    encoding: base64
+    header: X-Partner-Signature
+"#;
+        let mut temp_file = TempFileBuilder::new().suffix(".yaml").tempfile().unwrap();
+        write!(temp_file, "{}", yaml_content).unwrap();
+        temp_file.flush().unwrap();
+
+        let mut env = EnvironmentResolver::default();
+        env.set_variable("webhook_key", "k3y");
+        env.set_variable("partner_id", "acme");
+        let req_def = RequestParser::parse_file(temp_file.path()).unwrap().resolve_with_env(&env).unwrap();
+        match req_def.auth {
+            Some(crate::request::AuthConfig::Hmac(config)) => {
+                assert_eq!(config.algorithm, crate::request::HmacAlgorithm::Sha256);
+                assert_eq!(config.key, "k3y");
+                assert_eq!(config.canonical.as_deref(), Some("acme:{method}:{path}:{timestamp}:{body_hash}"));
+                assert_eq!(config.encoding, Some(crate::request::SignatureEncoding::Base64));
+            }
+            other => panic!("Expected HMAC auth, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_parse_file_tagged_json_body() {
+        let yaml_content = r#"
+name: "Body Request"
+method: POST
+url: "http://example.com/users"
+body:
+  json:
+    name: "John Doe"
+"#;
+        let mut temp_file = TempFileBuilder::new().suffix(".yaml").tempfile().unwrap();
+        write!(temp_file, "{}", yaml_content).unwrap();
+        temp_file.flush().unwrap();
+
+        let req_def = RequestParser::parse_file(temp_file.path()).unwrap();
+        match req_def.body {
+            Some(crate::request::RequestBody::Json(json)) => assert_eq!(json["name"], "John Doe"),
+            other => panic!("Expected JSON body, got {:?}", other),
+        }
+    }
+
     // Test for parse_directory
     #[test]
     fn test_parse_directory_multiple_files() {
@@ -1,6 +1,8 @@
 use std::path::{Path, PathBuf};
 use colored::*;
 
+use crate::environment::EnvironmentResolver;
+
 use super::{RequestDefinition, RequestParser, ValidationError};
 
 pub struct ValidationResult {
@@ -38,7 +40,7 @@ impl RequestValidator {
         let path = path.as_ref().to_path_buf();
         let mut result = ValidationResult::new(path.clone());
 
-        match RequestParser::parse_file(&path) {
+        match Self::parse(&path) {
             Ok(request) => {
                 if let Err(error) = request.validate() {
                     result.add_error(error)
@@ -55,11 +57,10 @@ impl RequestValidator {
     }
 
     pub fn validate_directory<P: AsRef<Path>>(dir: P) -> Vec<ValidationResult> {
-        let parse_results = RequestParser::parse_directory(dir);
         let mut validation_results = Vec::new();
 
-
-        for (path, parse_result) in parse_results {
+        for path in RequestParser::find_files(dir) {
+            let parse_result = Self::parse(&path);
             let mut result = ValidationResult::new(path.clone());
 
             match parse_result {
@@ -81,6 +82,13 @@ impl RequestValidator {
         validation_results
     }
 
+    /// Handlebars files are rendered against an empty, lenient environment,
+    /// which keeps every `{{variable}}` in place while loops and
+    /// conditionals shape the file.
+    fn parse(path: &Path) -> Result<RequestDefinition, ValidationError> {
+        RequestParser::parse_file_with_env(path, &EnvironmentResolver::default())
+    }
+
     fn check_warning(request: &RequestDefinition, result: &mut ValidationResult) {
         // Check for hardcoded URLs (should use templates)
         if !request.url.contains("{{") && 
@@ -91,15 +99,27 @@ impl RequestValidator {
         // Check for hardcoded auth tokens
         if let Some(auth) = &request.auth {
             match auth {
-                crate::request::AuthConfig::Bearer { token } => {
-                    if !token.contains("{{") {
-                        result.add_warning("Consider using environment variables for auth tokens".to_string());
-                    }
+                crate::request::AuthConfig::Bearer { token } if !token.contains("{{") => {
+                    result.add_warning("Consider using environment variables for auth tokens".to_string());
                 }
-                crate::request::AuthConfig::ApiKey { value, .. } => {
-                    if !value.contains("{{") {
-                        result.add_warning("Consider using environment variables for API keys".to_string());
-                    }
+                crate::request::AuthConfig::ApiKey { value, .. } if !value.contains("{{") => {
+                    result.add_warning("Consider using environment variables for API keys".to_string());
+                }
+                crate::request::AuthConfig::OAuth2(config)
+                    if config.client_secret.as_deref().is_some_and(|secret| !secret.contains("{{")) =>
+                {
+                    result.add_warning("Consider using environment variables for OAuth2 client secrets".to_string());
+                }
+                crate::request::AuthConfig::AwsSigV4(config) if !config.secret_key.contains("{{") => {
+                    result.add_warning("Consider using environment variables for AWS secret keys".to_string());
+                }
+                crate::request::AuthConfig::Hmac(config) if !config.key.contains("{{") => {
+                    result.add_warning("Consider using environment variables for HMAC keys".to_string());
+                }
+                crate::request::AuthConfig::Jwt(config)
+                    if config.secret.as_deref().is_some_and(|secret| !secret.contains("{{")) =>
+                {
+                    result.add_warning("Consider using environment variables for JWT secrets".to_string());
                 }
                 _ => {}
             }
@@ -0,0 +1,487 @@
+use colored::*;
+use regex::Regex;
+use reqwest::header::SET_COOKIE;
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use std::path::Path;
+
+use crate::request::{BodyTarget, ExecutionResult, JsonType, SchemaSource, TestAssertion};
+
+#[derive(Debug, Clone)]
+pub struct AssertionResult {
+    pub description: String,
+    pub passed: bool,
+    pub actual: Option<Value>,
+    pub message: Option<String>,
+}
+
+impl AssertionResult {
+    fn new(description: String, passed: bool, actual: Option<Value>) -> Self {
+        Self {
+            description,
+            passed,
+            actual,
+            message: None,
+        }
+    }
+
+    fn failed(description: String, message: String) -> Self {
+        Self {
+            description,
+            passed: false,
+            actual: None,
+            message: Some(message),
+        }
+    }
+
+    pub fn format(&self) -> String {
+        let actual = match (&self.message, &self.actual) {
+            (Some(message), _) => format!("({})", message),
+            (None, Some(actual)) => format!("(actual: {})", actual),
+            (None, None) => "(actual: nothing)".to_string(),
+        };
+
+        if self.passed {
+            format!("{} {} {}", "✔".green(), self.description, actual.dimmed())
+        } else {
+            format!("{} {} {}", "✘".red(), self.description.red(), actual.dimmed())
+        }
+    }
+}
+
+pub struct AssertionEngine;
+
+impl AssertionEngine {
+    /// Evaluates `tests` in order; schema files are relative to `base_dir`.
+    pub fn evaluate_all(tests: &[TestAssertion], base_dir: &Path, response: &ExecutionResult) -> Vec<AssertionResult> {
+        tests.iter()
+            .flat_map(|test| Self::evaluate(test, base_dir, response))
+            .collect()
+    }
+
+    /// Evaluates one `TestAssertion`. Every field that is set produces its own result.
+    pub fn evaluate(test: &TestAssertion, base_dir: &Path, response: &ExecutionResult) -> Vec<AssertionResult> {
+        let mut results = Vec::new();
+
+        if let Some(expected) = test.status_code {
+            let actual = response.status.as_u16();
+            results.push(AssertionResult::new(
+                format!("status code is {}", expected),
+                actual == expected,
+                Some(Value::from(actual)),
+            ));
+        }
+
+        if let Some(limit) = test.response_time_less_than {
+            let actual = response.elapsed.as_millis() as u64;
+            results.push(AssertionResult::new(
+                format!("response time is less than {} ms", limit),
+                actual < limit,
+                Some(Value::from(actual)),
+            ));
+        }
+
+        if let Some(path) = &test.json_path {
+            results.extend(Self::evaluate_json_path(path, test, response));
+        }
+
+        if let Some(source) = &test.schema {
+            results.extend(Self::evaluate_schema(source, base_dir, response));
+        }
+
+        if let Some(name) = &test.header {
+            let values: Vec<Value> = response.headers.get_all(name.as_str())
+                .iter()
+                .map(|value| Value::from(String::from_utf8_lossy(value.as_bytes()).into_owned()))
+                .collect();
+            let actual = match values.len() {
+                0 => None,
+                1 => values.into_iter().next(),
+                _ => Some(Value::Array(values)),
+            };
+            results.extend(Self::evaluate_value(&format!("header {}", name), actual.as_ref(), test));
+        }
+
+        if let Some(name) = &test.cookie {
+            let actual = Self::cookie(response, name).map(Value::from);
+            results.extend(Self::evaluate_value(&format!("cookie {}", name), actual.as_ref(), test));
+        }
+
+        if let Some(target) = test.body {
+            let (label, actual) = match target {
+                BodyTarget::Text => ("body", Value::from(response.body_text())),
+                BodyTarget::Size => ("body size", Value::from(response.body.len())),
+            };
+            results.extend(Self::evaluate_value(label, Some(&actual), test));
+        }
+
+        results
+    }
+
+    /// Validates the response body against a JSON Schema. Every violation
+    /// becomes its own failed result, named after its instance path.
+    fn evaluate_schema(source: &SchemaSource, base_dir: &Path, response: &ExecutionResult) -> Vec<AssertionResult> {
+        let label = match source {
+            SchemaSource::File(path) => format!("body matches schema {}", path),
+            SchemaSource::Inline(_) => "body matches inline schema".to_string(),
+        };
+
+        let validator = match source.validator(base_dir) {
+            Ok(validator) => validator,
+            Err(error) => return vec![AssertionResult::failed(label, error.to_string())],
+        };
+
+        let body = match response.body_json() {
+            Some(body) => body,
+            None => return vec![AssertionResult::failed(label, "response body is not valid JSON".to_string())],
+        };
+
+        let violations: Vec<AssertionResult> = validator.iter_errors(&body)
+            .map(|error| {
+                let instance_path = error.instance_path.to_string();
+                let instance_path = if instance_path.is_empty() { "/".to_string() } else { instance_path };
+                AssertionResult::failed(format!("{} at {}", label, instance_path), error.to_string())
+            })
+            .collect();
+
+        if violations.is_empty() {
+            vec![AssertionResult::new(label, true, None)]
+        } else {
+            violations
+        }
+    }
+
+    /// Looks up the value of cookie `name` in the `Set-Cookie` response headers.
+    fn cookie(response: &ExecutionResult, name: &str) -> Option<String> {
+        response.headers.get_all(SET_COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .filter_map(|value| value.split(';').next())
+            .filter_map(|pair| pair.split_once('='))
+            .find(|(cookie_name, _)| cookie_name.trim() == name)
+            .map(|(_, value)| value.trim().trim_matches('"').to_string())
+    }
+
+    fn evaluate_json_path(path: &str, test: &TestAssertion, response: &ExecutionResult) -> Vec<AssertionResult> {
+        let json_path = match JsonPath::parse(path) {
+            Ok(json_path) => json_path,
+            Err(error) => {
+                return vec![AssertionResult::failed(
+                    format!("{} is a valid JSONPath", path),
+                    error.to_string(),
+                )];
+            }
+        };
+
+        let body = match response.body_json() {
+            Some(body) => body,
+            None => {
+                return vec![AssertionResult::failed(
+                    format!("{} can be evaluated", path),
+                    "response body is not valid JSON".to_string(),
+                )];
+            }
+        };
+
+        let actual = Self::select(&json_path, &body);
+        Self::evaluate_value(path, actual.as_ref(), test)
+    }
+
+    /// Applies `exists` and every value matcher of `test` to `actual`, the
+    /// value selected from the response and described by `target`.
+    pub fn evaluate_value(target: &str, actual: Option<&Value>, test: &TestAssertion) -> Vec<AssertionResult> {
+        let mut results = Vec::new();
+        let owned = actual.cloned();
+        let mut check = |description: String, passed: bool| {
+            results.push(AssertionResult::new(description, passed, owned.clone()));
+        };
+
+        let expect_exists = test.exists.or(if test.has_value_matchers() { None } else { Some(true) });
+        if let Some(exists) = expect_exists {
+            if exists {
+                check(format!("{} exists", target), actual.is_some());
+            } else {
+                check(format!("{} does not exist", target), actual.is_none());
+            }
+        }
+
+        if let Some(expected) = &test.equals {
+            check(format!("{} equals {}", target, expected), actual == Some(expected));
+        }
+
+        if let Some(expected) = &test.not_equals {
+            check(format!("{} does not equal {}", target, expected), actual != Some(expected));
+        }
+
+        if let Some(expected) = &test.contains {
+            check(format!("{} contains {}", target, expected), actual.is_some_and(|a| Self::contains(a, expected)));
+        }
+
+        if let Some(pattern) = &test.matches {
+            let passed = match Regex::new(pattern) {
+                Ok(re) => actual.and_then(Self::as_text).is_some_and(|text| re.is_match(&text)),
+                Err(_) => false,
+            };
+            check(format!("{} matches /{}/", target, pattern), passed);
+        }
+
+        let number = actual.and_then(Value::as_f64);
+        if let Some(bound) = test.gt {
+            check(format!("{} > {}", target, bound), number.is_some_and(|n| n > bound));
+        }
+        if let Some(bound) = test.gte {
+            check(format!("{} >= {}", target, bound), number.is_some_and(|n| n >= bound));
+        }
+        if let Some(bound) = test.lt {
+            check(format!("{} < {}", target, bound), number.is_some_and(|n| n < bound));
+        }
+        if let Some(bound) = test.lte {
+            check(format!("{} <= {}", target, bound), number.is_some_and(|n| n <= bound));
+        }
+
+        if let Some(expected) = test.value_type {
+            check(format!("{} is of type {}", target, expected), actual.map(JsonType::of) == Some(expected));
+        }
+
+        if let Some(expected) = test.length {
+            check(format!("{} has length {}", target, expected), actual.and_then(Self::length) == Some(expected));
+        }
+
+        if let Some(options) = &test.one_of {
+            let listed = Value::Array(options.clone());
+            check(format!("{} is one of {}", target, listed), actual.is_some_and(|a| options.contains(a)));
+        }
+
+        if let Some(empty) = test.is_empty {
+            let is_empty = actual.is_none_or(|a| a.is_null() || Self::length(a) == Some(0));
+            let description = if empty {
+                format!("{} is empty", target)
+            } else {
+                format!("{} is not empty", target)
+            };
+            check(description, is_empty == empty);
+        }
+
+        results
+    }
+
+    fn contains(actual: &Value, expected: &Value) -> bool {
+        match (actual, expected) {
+            (Value::String(haystack), Value::String(needle)) => haystack.contains(needle.as_str()),
+            (Value::Array(items), _) => items.contains(expected),
+            (Value::Object(map), Value::String(key)) => map.contains_key(key),
+            _ => false,
+        }
+    }
+
+    fn as_text(value: &Value) -> Option<String> {
+        match value {
+            Value::String(text) => Some(text.clone()),
+            Value::Number(number) => Some(number.to_string()),
+            Value::Bool(flag) => Some(flag.to_string()),
+            _ => None,
+        }
+    }
+
+    fn length(value: &Value) -> Option<usize> {
+        match value {
+            Value::String(text) => Some(text.chars().count()),
+            Value::Array(items) => Some(items.len()),
+            Value::Object(map) => Some(map.len()),
+            _ => None,
+        }
+    }
+
+    /// Selects the value at `path`. A single match is returned as is, several
+    /// matches (wildcards, filters) are returned as an array.
+    pub fn select(path: &JsonPath, body: &Value) -> Option<Value> {
+        let nodes = path.query(body).all();
+        match nodes.len() {
+            0 => None,
+            1 => Some(nodes[0].clone()),
+            _ => Some(Value::Array(nodes.into_iter().cloned().collect())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::{header::HeaderMap, StatusCode};
+    use serde_json::json;
+    use std::time::Duration;
+
+    fn response(status: u16, body: Value) -> ExecutionResult {
+        ExecutionResult {
+            status: StatusCode::from_u16(status).unwrap(),
+            headers: HeaderMap::new(),
+            body: serde_json::to_vec(&body).unwrap(),
+            url: "http://example.com/".to_string(),
+            elapsed: Duration::from_millis(50),
+        }
+    }
+
+    #[test]
+    fn test_status_code_and_response_time() {
+        let response = response(200, json!({}));
+        let test = TestAssertion {
+            status_code: Some(201),
+            response_time_less_than: Some(100),
+            ..Default::default()
+        };
+
+        let results = AssertionEngine::evaluate(&test, Path::new(""), &response);
+        assert_eq!(results.len(), 2);
+        assert!(!results[0].passed);
+        assert_eq!(results[0].actual, Some(json!(200)));
+        assert!(results[1].passed);
+    }
+
+    #[test]
+    fn test_json_path_exists_and_equals() {
+        let response = response(200, json!({"data": [{"id": 7, "active": true}, {"id": 8, "active": false}]}));
+
+        let exists = TestAssertion { json_path: Some("$.data[0].id".to_string()), exists: Some(true), ..Default::default() };
+        assert!(AssertionEngine::evaluate(&exists, Path::new(""), &response)[0].passed);
+
+        let missing = TestAssertion { json_path: Some("$.token".to_string()), exists: Some(false), ..Default::default() };
+        assert!(AssertionEngine::evaluate(&missing, Path::new(""), &response)[0].passed);
+
+        let equals = TestAssertion { json_path: Some("$.data[1].id".to_string()), equals: Some(json!(8)), ..Default::default() };
+        let results = AssertionEngine::evaluate(&equals, Path::new(""), &response);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].passed);
+    }
+
+    #[test]
+    fn test_json_path_wildcards_and_filters() {
+        let response = response(200, json!({"data": [{"id": 7, "active": true}, {"id": 8, "active": false}]}));
+
+        let wildcard = TestAssertion { json_path: Some("$.data[*].id".to_string()), equals: Some(json!([7, 8])), ..Default::default() };
+        assert!(AssertionEngine::evaluate(&wildcard, Path::new(""), &response)[0].passed);
+
+        let filter = TestAssertion { json_path: Some("$.data[?@.active == false].id".to_string()), equals: Some(json!(8)), ..Default::default() };
+        assert!(AssertionEngine::evaluate(&filter, Path::new(""), &response)[0].passed);
+    }
+
+    #[test]
+    fn test_invalid_json_path_and_non_json_body_fail() {
+        let mut response = response(200, json!({}));
+
+        let invalid = TestAssertion { json_path: Some("data[".to_string()), ..Default::default() };
+        let results = AssertionEngine::evaluate(&invalid, Path::new(""), &response);
+        assert!(!results[0].passed);
+        assert!(results[0].message.is_some());
+
+        response.body = b"not json".to_vec();
+        let valid = TestAssertion { json_path: Some("$.id".to_string()), ..Default::default() };
+        assert!(!AssertionEngine::evaluate(&valid, Path::new(""), &response)[0].passed);
+    }
+
+    fn check(path: &str, test: TestAssertion) -> bool {
+        let response = response(200, json!({
+            "name": "John Doe",
+            "age": 42,
+            "role": "admin",
+            "tags": ["a", "b"],
+            "meta": {},
+            "deleted_at": null
+        }));
+        let test = TestAssertion { json_path: Some(path.to_string()), ..test };
+        AssertionEngine::evaluate(&test, Path::new(""), &response).iter().all(|r| r.passed)
+    }
+
+    #[test]
+    fn test_comparison_matchers() {
+        assert!(check("$.name", TestAssertion { not_equals: Some(json!("Jane")), ..Default::default() }));
+        assert!(check("$.name", TestAssertion { contains: Some(json!("Doe")), ..Default::default() }));
+        assert!(check("$.tags", TestAssertion { contains: Some(json!("b")), ..Default::default() }));
+        assert!(!check("$.tags", TestAssertion { contains: Some(json!("c")), ..Default::default() }));
+        assert!(check("$.name", TestAssertion { matches: Some("^John \\w+$".to_string()), ..Default::default() }));
+        assert!(check("$.age", TestAssertion { gt: Some(40.0), lte: Some(42.0), ..Default::default() }));
+        assert!(!check("$.age", TestAssertion { lt: Some(42.0), ..Default::default() }));
+        assert!(!check("$.name", TestAssertion { gte: Some(0.0), ..Default::default() }));
+        assert!(check("$.role", TestAssertion { one_of: Some(vec![json!("user"), json!("admin")]), ..Default::default() }));
+    }
+
+    #[test]
+    fn test_type_length_and_emptiness_matchers() {
+        assert!(check("$.tags", TestAssertion { value_type: Some(JsonType::Array), length: Some(2), ..Default::default() }));
+        assert!(check("$.deleted_at", TestAssertion { value_type: Some(JsonType::Null), ..Default::default() }));
+        assert!(!check("$.age", TestAssertion { value_type: Some(JsonType::String), ..Default::default() }));
+        assert!(check("$.name", TestAssertion { length: Some(8), ..Default::default() }));
+        assert!(check("$.meta", TestAssertion { is_empty: Some(true), ..Default::default() }));
+        assert!(check("$.tags", TestAssertion { is_empty: Some(false), ..Default::default() }));
+        assert!(check("$.missing", TestAssertion { is_empty: Some(true), ..Default::default() }));
+    }
+
+    #[test]
+    fn test_header_cookie_and_body_targets() {
+        let mut response = response(200, json!({}));
+        response.body = b"Hello plain world".to_vec();
+        response.headers.insert("Content-Type", "application/json; charset=utf-8".parse().unwrap());
+        response.headers.insert("Cache-Control", "no-cache".parse().unwrap());
+        response.headers.append(SET_COOKIE, "session=abc123; Path=/; HttpOnly".parse().unwrap());
+        response.headers.append(SET_COOKIE, "theme=dark".parse().unwrap());
+
+        let passes = |test: TestAssertion| AssertionEngine::evaluate(&test, Path::new(""), &response).iter().all(|r| r.passed);
+
+        assert!(passes(TestAssertion { header: Some("content-type".to_string()), contains: Some(json!("application/json")), ..Default::default() }));
+        assert!(passes(TestAssertion { header: Some("Cache-Control".to_string()), ..Default::default() }));
+        assert!(passes(TestAssertion { header: Some("ETag".to_string()), exists: Some(false), ..Default::default() }));
+        assert!(passes(TestAssertion { cookie: Some("session".to_string()), equals: Some(json!("abc123")), ..Default::default() }));
+        assert!(!passes(TestAssertion { cookie: Some("missing".to_string()), ..Default::default() }));
+        assert!(passes(TestAssertion { body: Some(BodyTarget::Text), contains: Some(json!("plain")), ..Default::default() }));
+        assert!(passes(TestAssertion { body: Some(BodyTarget::Text), matches: Some("^Hello .* world$".to_string()), ..Default::default() }));
+        assert!(passes(TestAssertion { body: Some(BodyTarget::Size), equals: Some(json!(17)), lt: Some(100.0), ..Default::default() }));
+    }
+
+    #[test]
+    fn test_schema_inline_draft_2020_12_lists_every_violation() {
+        let schema = json!({
+            "$schema": "https://json-schema.org/draft/2020-12/schema",
+            "type": "object",
+            "required": ["id", "name"],
+            "properties": {
+                "id": {"type": "integer"},
+                "tags": {"type": "array", "items": {"type": "string"}}
+            }
+        });
+        let test = TestAssertion { schema: Some(SchemaSource::Inline(schema)), ..Default::default() };
+
+        let valid = response(200, json!({"id": 1, "name": "John", "tags": ["a"]}));
+        let results = AssertionEngine::evaluate(&test, Path::new(""), &valid);
+        assert_eq!(results.len(), 1);
+        assert!(results[0].passed);
+
+        let invalid = response(200, json!({"id": "1", "tags": ["a", 2]}));
+        let results = AssertionEngine::evaluate(&test, Path::new(""), &invalid);
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|r| !r.passed));
+        assert!(results.iter().any(|r| r.description.ends_with("at /id")));
+        assert!(results.iter().any(|r| r.description.ends_with("at /tags/1")));
+        assert!(results.iter().any(|r| r.description.ends_with("at /")));
+    }
+
+    #[test]
+    fn test_schema_from_draft_07_file() {
+        use std::io::Write;
+
+        let mut schema_file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
+        write!(schema_file, "{}", json!({
+            "$schema": "http://json-schema.org/draft-07/schema#",
+            "type": "object",
+            "properties": {"token": {"type": "string", "minLength": 3}},
+            "required": ["token"]
+        })).unwrap();
+        schema_file.flush().unwrap();
+
+        let path = schema_file.path().to_string_lossy().into_owned();
+        let test = TestAssertion { schema: Some(SchemaSource::File(path)), ..Default::default() };
+
+        assert!(AssertionEngine::evaluate(&test, Path::new(""), &response(200, json!({"token": "abcdef"})))[0].passed);
+        let results = AssertionEngine::evaluate(&test, Path::new(""), &response(200, json!({"token": "ab"})));
+        assert!(!results[0].passed);
+        assert!(results[0].description.ends_with("at /token"));
+    }
+}
@@ -0,0 +1,117 @@
+use regex::Regex;
+use serde_json::Value;
+use serde_json_path::JsonPath;
+use std::collections::HashMap;
+
+use crate::request::{Capture, ExecutionResult};
+use crate::response::AssertionEngine;
+
+pub struct ValueExtractor;
+
+impl ValueExtractor {
+    /// Captures every variable in `extract` from `response`. Variables that
+    /// cannot be found are reported together as one error.
+    pub fn extract_all(
+        extract: &HashMap<String, Capture>,
+        response: &ExecutionResult,
+    ) -> Result<HashMap<String, String>, String> {
+        let mut captured = HashMap::new();
+        let mut missing = Vec::new();
+
+        for (name, capture) in extract {
+            match Self::extract(capture, response) {
+                Ok(value) => {
+                    captured.insert(name.clone(), value);
+                }
+                Err(error) => missing.push(format!("{}: {}", name, error)),
+            }
+        }
+
+        if missing.is_empty() {
+            Ok(captured)
+        } else {
+            missing.sort();
+            Err(format!("Failed to capture {}", missing.join("; ")))
+        }
+    }
+
+    pub fn extract(capture: &Capture, response: &ExecutionResult) -> Result<String, String> {
+        if let Some(path) = &capture.json_path {
+            let json_path = JsonPath::parse(path).map_err(|e| e.to_string())?;
+            let body = response.body_json().ok_or("response body is not valid JSON")?;
+            return match AssertionEngine::select(&json_path, &body) {
+                Some(Value::String(text)) => Ok(text),
+                Some(value) => Ok(value.to_string()),
+                None => Err(format!("{} matched nothing", path)),
+            };
+        }
+
+        if let Some(name) = &capture.header {
+            return response.headers.get(name.as_str())
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+                .ok_or_else(|| format!("header {} not present", name));
+        }
+
+        if let Some(pattern) = &capture.regex {
+            let re = Regex::new(pattern).map_err(|e| e.to_string())?;
+            let body = response.body_text();
+            let caps = re.captures(&body).ok_or_else(|| format!("/{}/ did not match", pattern))?;
+            let group = capture.group.unwrap_or(if re.captures_len() > 1 { 1 } else { 0 });
+            return caps.get(group)
+                .map(|m| m.as_str().to_string())
+                .ok_or_else(|| format!("/{}/ has no group {}", pattern, group));
+        }
+
+        Err("no json_path, header or regex given".to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::{header::HeaderMap, StatusCode};
+    use std::time::Duration;
+
+    fn response(body: &str) -> ExecutionResult {
+        let mut headers = HeaderMap::new();
+        headers.insert("X-Request-Id", "req-42".parse().unwrap());
+        ExecutionResult {
+            status: StatusCode::OK,
+            headers,
+            body: body.as_bytes().to_vec(),
+            url: "http://example.com/".to_string(),
+            elapsed: Duration::from_millis(5),
+        }
+    }
+
+    #[test]
+    fn test_extract_json_path_header_and_regex() {
+        let response = response(r#"{"token":"abc","user":{"id":7}}"#);
+        let extract = HashMap::from([
+            ("auth_token".to_string(), Capture { json_path: Some("$.token".to_string()), ..Default::default() }),
+            ("user_id".to_string(), Capture { json_path: Some("$.user.id".to_string()), ..Default::default() }),
+            ("request_id".to_string(), Capture { header: Some("x-request-id".to_string()), ..Default::default() }),
+            ("token_again".to_string(), Capture { regex: Some(r#""token":"(\w+)""#.to_string()), ..Default::default() }),
+        ]);
+
+        let captured = ValueExtractor::extract_all(&extract, &response).unwrap();
+        assert_eq!(captured["auth_token"], "abc");
+        assert_eq!(captured["user_id"], "7");
+        assert_eq!(captured["request_id"], "req-42");
+        assert_eq!(captured["token_again"], "abc");
+    }
+
+    #[test]
+    fn test_extract_reports_missing_values() {
+        let response = response("plain text");
+        let extract = HashMap::from([
+            ("a".to_string(), Capture { json_path: Some("$.token".to_string()), ..Default::default() }),
+            ("b".to_string(), Capture { header: Some("X-Missing".to_string()), ..Default::default() }),
+        ]);
+
+        let error = ValueExtractor::extract_all(&extract, &response).unwrap_err();
+        assert!(error.contains("a: response body is not valid JSON"));
+        assert!(error.contains("b: header X-Missing not present"));
+    }
+}
@@ -1,12 +1,50 @@
 use anyhow::Result;
 use colored::*;
 
+use crate::request::ExecutionResult;
+
 pub struct ResponseFormatter;
 
 impl ResponseFormatter {
-    pub fn format_response(response: &reqwest::Response, body: &str) -> Result<String> {
-        // TODO: Implement response formatting
-        Ok(format!("Status: {}\nBody: {}", response.status(), body))
+    pub fn format_response(result: &ExecutionResult) -> Result<String> {
+        let mut output = String::new();
+
+        let status = result.status.to_string();
+        let status = if result.status.is_success() {
+            status.green()
+        } else if result.status.is_redirection() {
+            status.yellow()
+        } else {
+            status.red()
+        };
+        output.push_str(&format!(
+            "{} {} {}\n",
+            "Status:".bold(),
+            status.bold(),
+            format!("({} ms)", result.elapsed.as_millis()).dimmed()
+        ));
+        output.push_str(&format!("{} {}\n", "URL:".bold(), result.url));
+
+        output.push_str(&format!("{}\n", "Headers:".bold()));
+        for (name, value) in &result.headers {
+            output.push_str(&format!(
+                "  {}: {}\n",
+                name.as_str().cyan(),
+                value.to_str().unwrap_or("<binary>")
+            ));
+        }
+
+        let body = result.body_text();
+        if !body.is_empty() {
+            output.push_str(&format!("{}\n", "Body:".bold()));
+            match Self::format_json(&body) {
+                Ok(pretty) => output.push_str(&pretty),
+                Err(_) => output.push_str(&body),
+            }
+            output.push('\n');
+        }
+
+        Ok(output)
     }
 
     pub fn format_json(json: &str) -> Result<String> {
@@ -14,3 +52,26 @@ impl ResponseFormatter {
         Ok(serde_json::to_string_pretty(&parsed)?)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::{header::HeaderMap, StatusCode};
+    use std::time::Duration;
+
+    #[test]
+    fn test_format_response_pretty_prints_json_body() {
+        colored::control::set_override(false);
+        let result = ExecutionResult {
+            status: StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: br#"{"id":1}"#.to_vec(),
+            url: "http://example.com/".to_string(),
+            elapsed: Duration::from_millis(12),
+        };
+
+        let output = ResponseFormatter::format_response(&result).unwrap();
+        assert!(output.contains("Status: 200 OK (12 ms)"));
+        assert!(output.contains("{\n  \"id\": 1\n}"));
+    }
+}
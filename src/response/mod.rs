@@ -1,2 +1,6 @@
 pub mod formatter;
+pub mod assertions;
+pub mod extractor;
 pub use formatter::*;
+pub use assertions::*;
+pub use extractor::*;
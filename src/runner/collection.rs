@@ -0,0 +1,605 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, RwLock};
+use colored::*;
+use indicatif::{ProgressBar, ProgressStyle};
+use tokio::sync::Semaphore;
+use tokio::task::{self, JoinError, JoinSet};
+
+use crate::environment::{EnvironmentResolver, TemplateRenderer};
+use crate::request::{
+    ExecutionResult, RequestDefinition, RequestExecutor, RequestParser, ValidationError,
+};
+use crate::response::{AssertionEngine, AssertionResult, ResponseFormatter, ValueExtractor};
+use crate::utils::{parse_file_content, read_file};
+
+pub struct RunResult {
+    pub file_path: PathBuf,
+    pub name: Option<String>,
+    pub response: Option<ExecutionResult>,
+    pub assertions: Vec<AssertionResult>,
+    pub captured: Vec<(String, String)>,
+    pub errors: Vec<String>,
+}
+
+impl RunResult {
+    pub fn new(file_path: PathBuf) -> Self {
+        Self {
+            file_path,
+            name: None,
+            response: None,
+            assertions: Vec::new(),
+            captured: Vec::new(),
+            errors: Vec::new(),
+        }
+    }
+
+    pub fn add_error(&mut self, error: String) {
+        self.errors.push(error);
+    }
+
+    pub fn passed(&self) -> bool {
+        self.errors.is_empty()
+            && self.response.is_some()
+            && self.assertions.iter().all(|assertion| assertion.passed)
+    }
+}
+
+pub struct CollectionRunner;
+
+impl CollectionRunner {
+    /// Runs every request file under `dir`, at most `concurrency` at a time.
+    ///
+    /// Results are always returned sorted by path, whatever order the
+    /// requests finished in. A request that captures values with `extract`
+    /// runs on its own: the requests before it finish first and the ones
+    /// after it wait, so captured values reach later requests whatever the
+    /// concurrency.
+    pub async fn run_directory<P: AsRef<Path>>(
+        dir: P,
+        env_resolver: &EnvironmentResolver,
+        executor: &RequestExecutor,
+        concurrency: usize,
+    ) -> Vec<RunResult> {
+        let mut paths = RequestParser::find_files(dir);
+        paths.sort();
+
+        let progress = ProgressBar::new(paths.len() as u64);
+        progress.set_style(
+            ProgressStyle::with_template("  {spinner} [{bar:30}] {pos}/{len} {msg}")
+                .unwrap_or_else(|_| ProgressStyle::default_bar())
+                .progress_chars("=> "),
+        );
+
+        let env_resolver = Arc::new(RwLock::new(env_resolver.clone()));
+        let semaphore = Arc::new(Semaphore::new(concurrency.max(1)));
+        let mut tasks = JoinSet::new();
+        let mut task_indexes = HashMap::new();
+        let mut run_results: Vec<Option<RunResult>> = paths.iter().map(|_| None).collect();
+
+        for (index, path) in paths.iter().enumerate() {
+            let captures = Self::captures_values(path);
+            if captures {
+                while let Some(joined) = tasks.join_next().await {
+                    Self::record(joined, &task_indexes, &paths, &mut run_results, &progress);
+                }
+            }
+
+            let permit = match semaphore.clone().acquire_owned().await {
+                Ok(permit) => permit,
+                Err(_) => break,
+            };
+            let path = path.clone();
+            let env_resolver = env_resolver.clone();
+            let executor = executor.clone();
+
+            let handle = tasks.spawn(async move {
+                let _permit = permit;
+                let result = Self::run_request(path, &env_resolver, &executor).await;
+                (index, result)
+            });
+            task_indexes.insert(handle.id(), index);
+
+            if captures {
+                while let Some(joined) = tasks.join_next().await {
+                    Self::record(joined, &task_indexes, &paths, &mut run_results, &progress);
+                }
+            }
+            while let Some(joined) = tasks.try_join_next() {
+                Self::record(joined, &task_indexes, &paths, &mut run_results, &progress);
+            }
+        }
+
+        while let Some(joined) = tasks.join_next().await {
+            Self::record(joined, &task_indexes, &paths, &mut run_results, &progress);
+        }
+        progress.finish_and_clear();
+
+        run_results.into_iter().flatten().collect()
+    }
+
+    /// Stores a finished task's result; a task that panicked or was
+    /// cancelled becomes a failed result for its file.
+    fn record(
+        joined: Result<(usize, RunResult), JoinError>,
+        task_indexes: &HashMap<task::Id, usize>,
+        paths: &[PathBuf],
+        run_results: &mut [Option<RunResult>],
+        progress: &ProgressBar,
+    ) {
+        progress.inc(1);
+        match joined {
+            Ok((index, result)) => run_results[index] = Some(result),
+            Err(error) => {
+                if let Some(&index) = task_indexes.get(&error.id()) {
+                    let mut result = RunResult::new(paths[index].clone());
+                    result.add_error(format!("Request task failed: {}", error));
+                    run_results[index] = Some(result);
+                }
+            }
+        }
+    }
+
+    /// Whether the request at `path` captures values. Handlebars files can
+    /// only be read once rendered, so they are assumed to.
+    fn captures_values(path: &Path) -> bool {
+        let Ok(content) = read_file(path) else { return false };
+        if TemplateRenderer::is_handlebars(&content) {
+            return true;
+        }
+        parse_file_content::<serde_yaml::Value, _>(path, &content)
+            .map(|value| value.get("extract").is_some())
+            .unwrap_or(false)
+    }
+
+    async fn run_request(
+        path: PathBuf,
+        env_resolver: &RwLock<EnvironmentResolver>,
+        executor: &RequestExecutor,
+    ) -> RunResult {
+        let mut result = RunResult::new(path);
+
+        // Parsed here rather than up front so templated files see values
+        // captured by earlier requests.
+        let resolved = match env_resolver.read() {
+            Ok(env_resolver) => Self::prepare_request(&result.file_path, &env_resolver),
+            Err(poisoned) => Self::prepare_request(&result.file_path, &poisoned.into_inner()),
+        };
+        let resolved = match resolved {
+            Ok(resolved) => resolved,
+            Err(error) => {
+                result.add_error(error.to_string());
+                return result;
+            }
+        };
+
+        result.name = Some(resolved.name.clone());
+        Self::execute_resolved(&mut result, &resolved, executor).await;
+
+        if !result.captured.is_empty() {
+            if let Ok(mut env_resolver) = env_resolver.write() {
+                for (name, value) in &result.captured {
+                    env_resolver.set_variable(name.clone(), value.clone());
+                }
+            }
+        }
+
+        result
+    }
+
+    fn prepare_request(path: &Path, env_resolver: &EnvironmentResolver) -> Result<RequestDefinition, ValidationError> {
+        RequestParser::parse_file_with_env(path, env_resolver)?.resolve_with_env(env_resolver)
+    }
+
+    /// Sends `resolved`, then records its assertions and captured values in
+    /// `result`. Without tests, any 4xx/5xx status counts as a failure.
+    pub(crate) async fn execute_resolved(
+        result: &mut RunResult,
+        resolved: &RequestDefinition,
+        executor: &RequestExecutor,
+    ) {
+        let response = match executor.execute(resolved).await {
+            Ok(response) => response,
+            Err(error) => {
+                result.add_error(format!("{:#}", error));
+                return;
+            }
+        };
+
+        match resolved.tests.as_deref() {
+            Some(tests) if !tests.is_empty() => {
+                result.assertions = AssertionEngine::evaluate_all(tests, &resolved.base_dir, &response);
+            }
+            _ => {
+                if response.status.is_client_error() || response.status.is_server_error() {
+                    result.add_error(format!("Request returned {}", response.status));
+                }
+            }
+        }
+
+        if let Some(extract) = &resolved.extract {
+            match ValueExtractor::extract_all(extract, &response) {
+                Ok(captured) => {
+                    let mut captured: Vec<_> = captured.into_iter().collect();
+                    captured.sort();
+                    result.captured = captured;
+                }
+                Err(error) => result.add_error(error),
+            }
+        }
+
+        result.response = Some(response);
+    }
+
+    pub fn print_run_result(result: &RunResult, verbose: bool) {
+        let file_name = result.file_path.file_name()
+            .unwrap_or_default()
+            .to_string_lossy();
+        let label = match &result.name {
+            Some(name) => format!("{} {}", name, format!("({})", file_name).dimmed()),
+            None => file_name.to_string(),
+        };
+
+        let timing = result.response.as_ref()
+            .map(|response| format!("{} {} ms", response.status, response.elapsed.as_millis()))
+            .unwrap_or_default();
+
+        if result.passed() {
+            println!("  {} {} {}", "✅".green(), label.green(), timing.dimmed());
+        } else {
+            println!("  {} {} {}", "❌".red(), label.red(), timing.dimmed());
+            for error in &result.errors {
+                println!("     {} {}", "❌".red(), error.red());
+            }
+        }
+
+        for assertion in &result.assertions {
+            if verbose || !assertion.passed {
+                println!("     {}", assertion.format());
+            }
+        }
+
+        if verbose {
+            for (name, value) in &result.captured {
+                println!("     {} {} = {}", "📌".cyan(), name.cyan(), value);
+            }
+        }
+
+        if verbose {
+            if let Some(response) = &result.response {
+                if let Ok(formatted) = ResponseFormatter::format_response(response) {
+                    for line in formatted.lines() {
+                        println!("     {}", line);
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn print_run_results(results: &[RunResult], verbose: bool) {
+        println!("{}", "🚀 Run Results".bold().cyan());
+        println!();
+
+        for result in results {
+            Self::print_run_result(result, verbose);
+        }
+
+        let total = results.len();
+        let passed = results.iter().filter(|r| r.passed()).count();
+
+        println!();
+        println!("{}", "📊 Summary".bold().cyan());
+        println!("  Requests run: {}", total);
+        println!("  Passed: {} {}",
+            passed.to_string().green(),
+            if passed == total { "🎉" } else { "" });
+        println!("  Failed: {}", (total - passed).to_string().red());
+
+        let assertions: Vec<_> = results.iter().flat_map(|r| &r.assertions).collect();
+        if !assertions.is_empty() {
+            let passed_assertions = assertions.iter().filter(|a| a.passed).count();
+            println!("  Assertions: {} passed, {} failed",
+                passed_assertions.to_string().green(),
+                (assertions.len() - passed_assertions).to_string().red());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::OAuth2TokenCache;
+    use std::io::Write;
+    use tempfile::Builder as TempFileBuilder;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, Request, Respond, ResponseTemplate};
+
+    /// Answers after `delay` and records when each request arrived. A
+    /// request that arrives less than `delay` after another was sent while
+    /// that one was still in flight.
+    struct DelayedArrivals {
+        delay: std::time::Duration,
+        arrivals: Arc<std::sync::Mutex<Vec<std::time::Instant>>>,
+    }
+
+    impl Respond for DelayedArrivals {
+        fn respond(&self, _: &Request) -> ResponseTemplate {
+            self.arrivals.lock().unwrap().push(std::time::Instant::now());
+            ResponseTemplate::new(200).set_delay(self.delay)
+        }
+    }
+
+    fn write_request(dir: &Path, file_name: &str, content: &str) {
+        let mut file = std::fs::File::create(dir.join(file_name)).unwrap();
+        write!(file, "{}", content).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_run_directory_sorted_with_pass_and_fail() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/ok"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/missing"))
+            .respond_with(ResponseTemplate::new(404))
+            .mount(&server)
+            .await;
+
+        let temp_dir = TempFileBuilder::new().prefix("test_collection_").tempdir().unwrap();
+        let dir_path = temp_dir.path();
+        write_request(dir_path, "02-missing.yaml",
+            &format!("name: Missing\nmethod: GET\nurl: {}/missing", server.uri()));
+        write_request(dir_path, "01-ok.yaml",
+            &format!("name: Ok\nmethod: GET\nurl: {}/ok", server.uri()));
+        write_request(dir_path, "03-broken.yaml", "name: Broken\nmethod: FETCH\nurl: nowhere");
+
+        let results = CollectionRunner::run_directory(
+            dir_path,
+            &EnvironmentResolver::default(),
+            &RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory()),
+            1,
+        ).await;
+
+        let names: Vec<_> = results.iter()
+            .map(|r| r.file_path.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, vec!["01-ok.yaml", "02-missing.yaml", "03-broken.yaml"]);
+        assert!(results[0].passed());
+        assert!(!results[1].passed());
+        assert!(results[1].response.is_some());
+        assert!(!results[2].passed());
+        assert!(results[2].response.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_run_directory_skips_workflow_and_environment_files() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/ok"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(1)
+            .mount(&server)
+            .await;
+
+        let temp_dir = TempFileBuilder::new().prefix("test_collection_skip_").tempdir().unwrap();
+        let dir_path = temp_dir.path();
+        write_request(dir_path, "ok.yaml", &format!("name: Ok\nmethod: GET\nurl: {}/ok", server.uri()));
+        write_request(dir_path, "flow.yaml", "name: Flow\nsteps:\n  - name: ok\n    request: ok.yaml");
+        std::fs::create_dir(dir_path.join("environments")).unwrap();
+        write_request(&dir_path.join("environments"), "dev.yaml", "name: dev\nvariables:\n  base_url: http://dev");
+
+        let results = CollectionRunner::run_directory(
+            dir_path,
+            &EnvironmentResolver::default(),
+            &RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory()),
+            1,
+        ).await;
+
+        assert_eq!(results.len(), 1);
+        assert!(results[0].passed());
+    }
+
+    #[tokio::test]
+    async fn test_run_directory_concurrent_keeps_sorted_order() {
+        let server = MockServer::start().await;
+        let delay = std::time::Duration::from_millis(500);
+        let arrivals = Arc::new(std::sync::Mutex::new(Vec::new()));
+        Mock::given(method("GET"))
+            .and(path("/slow"))
+            .respond_with(DelayedArrivals { delay, arrivals: arrivals.clone() })
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/fast"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let temp_dir = TempFileBuilder::new().prefix("test_concurrent_").tempdir().unwrap();
+        let dir_path = temp_dir.path();
+        for i in 0..4 {
+            let endpoint = if i % 2 == 0 { "slow" } else { "fast" };
+            write_request(dir_path, &format!("{:02}-{}.yaml", i, endpoint),
+                &format!("name: Request {}\nmethod: GET\nurl: {}/{}", i, server.uri(), endpoint));
+        }
+
+        let results = CollectionRunner::run_directory(
+            dir_path,
+            &EnvironmentResolver::default(),
+            &RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory()),
+            4,
+        ).await;
+
+        let names: Vec<_> = results.iter().map(|r| r.name.clone().unwrap()).collect();
+        assert_eq!(names, vec!["Request 0", "Request 1", "Request 2", "Request 3"]);
+        assert!(results.iter().all(|r| r.passed()));
+
+        let arrivals = arrivals.lock().unwrap();
+        assert_eq!(arrivals.len(), 2);
+        let gap = arrivals[1].duration_since(arrivals[0]);
+        assert!(gap < delay, "both slow requests should be in flight at once, second arrived {:?} after the first", gap);
+    }
+
+    #[tokio::test]
+    async fn test_run_directory_assertions_decide_outcome() {
+        let server = MockServer::start().await;
+        Mock::given(method("GET"))
+            .and(path("/missing"))
+            .respond_with(ResponseTemplate::new(404).set_body_string(r#"{"error":"not found"}"#))
+            .mount(&server)
+            .await;
+
+        let temp_dir = TempFileBuilder::new().prefix("test_assertions_").tempdir().unwrap();
+        let dir_path = temp_dir.path();
+        write_request(dir_path, "expected-404.yaml", &format!(
+            "name: Expected\nmethod: GET\nurl: {}/missing\ntests:\n  - status_code: 404\n  - json_path: \"$.error\"\n    equals: \"not found\"",
+            server.uri()));
+        write_request(dir_path, "wrong-body.yaml", &format!(
+            "name: Wrong\nmethod: GET\nurl: {}/missing\ntests:\n  - status_code: 404\n  - json_path: \"$.id\"\n    exists: true",
+            server.uri()));
+
+        let results = CollectionRunner::run_directory(
+            dir_path,
+            &EnvironmentResolver::default(),
+            &RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory()),
+            1,
+        ).await;
+
+        assert!(results[0].passed());
+        assert_eq!(results[0].assertions.len(), 2);
+        assert!(!results[1].passed());
+        assert!(results[1].errors.is_empty());
+        assert!(!results[1].assertions[1].passed);
+    }
+
+    #[tokio::test]
+    async fn test_run_directory_records_captured_values() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/auth/login"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"token":"abc123"}"#))
+            .mount(&server)
+            .await;
+
+        let temp_dir = TempFileBuilder::new().prefix("test_capture_").tempdir().unwrap();
+        let dir_path = temp_dir.path();
+        write_request(dir_path, "login.yaml", &format!(
+            "name: Login\nmethod: POST\nurl: {}/auth/login\nextract:\n  auth_token:\n    json_path: \"$.token\"",
+            server.uri()));
+
+        let results = CollectionRunner::run_directory(
+            dir_path,
+            &EnvironmentResolver::default(),
+            &RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory()),
+            1,
+        ).await;
+
+        assert!(results[0].passed());
+        assert_eq!(results[0].captured, vec![("auth_token".to_string(), "abc123".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn test_run_directory_captured_values_reach_later_requests() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/auth/login"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"token":"abc123"}"#))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/me"))
+            .and(wiremock::matchers::header("Authorization", "Bearer abc123"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let temp_dir = TempFileBuilder::new().prefix("test_capture_flow_").tempdir().unwrap();
+        let dir_path = temp_dir.path();
+        write_request(dir_path, "01-login.yaml",
+            "name: Login\nmethod: POST\nurl: \"{{base_url}}/auth/login\"\nextract:\n  auth_token:\n    json_path: \"$.token\"");
+        write_request(dir_path, "02-me.yaml",
+            "name: Me\nmethod: GET\nurl: \"{{base_url}}/me\"\nheaders:\n  Authorization: \"Bearer {{auth_token}}\"");
+
+        let mut env_resolver = EnvironmentResolver::default();
+        env_resolver.set_variable("base_url", server.uri());
+
+        let executor = RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory());
+        let results = CollectionRunner::run_directory(dir_path, &env_resolver, &executor, 1).await;
+
+        assert!(results[0].passed());
+        assert!(results[1].passed(), "errors: {:?}", results[1].errors);
+    }
+
+    #[tokio::test]
+    async fn test_run_directory_concurrent_waits_for_captures() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/auth/login"))
+            .respond_with(ResponseTemplate::new(200)
+                .set_body_string(r#"{"token":"abc123"}"#)
+                .set_delay(std::time::Duration::from_millis(200)))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/me"))
+            .and(wiremock::matchers::header("Authorization", "Bearer abc123"))
+            .respond_with(ResponseTemplate::new(200))
+            .expect(2)
+            .mount(&server)
+            .await;
+
+        let temp_dir = TempFileBuilder::new().prefix("test_capture_concurrent_").tempdir().unwrap();
+        let dir_path = temp_dir.path();
+        write_request(dir_path, "01-login.yaml",
+            "name: Login\nmethod: POST\nurl: \"{{base_url}}/auth/login\"\nextract:\n  auth_token:\n    json_path: \"$.token\"");
+        for name in ["02-me.yaml", "03-me-again.yaml"] {
+            write_request(dir_path, name,
+                "name: Me\nmethod: GET\nurl: \"{{base_url}}/me\"\nheaders:\n  Authorization: \"Bearer {{auth_token}}\"");
+        }
+
+        let mut env_resolver = EnvironmentResolver::default();
+        env_resolver.set_variable("base_url", server.uri());
+
+        let executor = RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory());
+        let results = CollectionRunner::run_directory(dir_path, &env_resolver, &executor, 4).await;
+
+        assert_eq!(results.len(), 3);
+        assert!(results.iter().all(|r| r.passed()), "errors: {:?}", results.iter().map(|r| &r.errors).collect::<Vec<_>>());
+    }
+
+    #[tokio::test]
+    async fn test_run_directory_renders_handlebars_requests_with_captures() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/auth/login"))
+            .respond_with(ResponseTemplate::new(200).set_body_string(r#"{"token":"abc123","roles":["admin","dev"]}"#))
+            .mount(&server)
+            .await;
+        Mock::given(method("POST"))
+            .and(path("/grants"))
+            .and(wiremock::matchers::header("Authorization", "Bearer abc123"))
+            .and(wiremock::matchers::body_json(serde_json::json!({"roles": ["ADMIN", "DEV"]})))
+            .respond_with(ResponseTemplate::new(204))
+            .mount(&server)
+            .await;
+
+        let temp_dir = TempFileBuilder::new().prefix("test_handlebars_").tempdir().unwrap();
+        let dir_path = temp_dir.path();
+        write_request(dir_path, "01-login.yaml",
+            "name: Login\nmethod: POST\nurl: \"{{base_url}}/auth/login\"\nextract:\n  auth_token:\n    json_path: \"$.token\"\n  roles:\n    json_path: \"$.roles\"");
+        write_request(dir_path, "02-grants.yaml",
+            "template: handlebars\nname: Grants\nmethod: POST\nurl: \"{{base_url}}/grants\"\nheaders:\n  Authorization: \"Bearer {{auth_token}}\"\nbody:\n  json:\n    roles:\n{{#each roles}}      - \"{{upper this}}\"\n{{/each}}");
+
+        let mut env_resolver = EnvironmentResolver::default();
+        env_resolver.set_variable("base_url", server.uri());
+
+        let executor = RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory());
+        let results = CollectionRunner::run_directory(dir_path, &env_resolver, &executor, 1).await;
+
+        assert!(results[0].passed());
+        assert!(results[1].passed(), "errors: {:?}", results[1].errors);
+    }
+}
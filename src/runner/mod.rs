@@ -0,0 +1,4 @@
+pub mod collection;
+pub mod workflow;
+pub use collection::*;
+pub use workflow::*;
@@ -0,0 +1,343 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::environment::EnvironmentResolver;
+use crate::request::{RequestDefinition, RequestExecutor, RequestParser, TestAssertion, ValidationError};
+use crate::utils::load_and_parse_file;
+
+use super::{CollectionRunner, RunResult};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Workflow {
+    pub name: String,
+    pub steps: Vec<WorkflowStep>,
+    /// Directory request paths are relative to; the workflow file's directory.
+    #[serde(skip)]
+    pub base_dir: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WorkflowStep {
+    pub id: String,
+    pub request: String,
+    pub depends_on: Option<Vec<String>>,
+    pub variables: Option<HashMap<String, String>>,
+    pub tests: Option<Vec<TestAssertion>>,
+}
+
+impl Workflow {
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self, ValidationError> {
+        let path = path.as_ref();
+        let mut workflow: Workflow = load_and_parse_file(path)?;
+        workflow.base_dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+        Ok(workflow)
+    }
+
+    /// True when the file at `path` looks like a workflow (it has `steps`)
+    /// rather than a single request.
+    pub fn is_workflow_file<P: AsRef<Path>>(path: P) -> bool {
+        load_and_parse_file::<serde_json::Value, _>(path)
+            .map(|value| value.get("steps").is_some())
+            .unwrap_or(false)
+    }
+
+    pub fn request_path(&self, step: &WorkflowStep) -> PathBuf {
+        self.base_dir.join(&step.request)
+    }
+
+    pub fn validate(&self) -> Result<(), ValidationError> {
+        if self.name.trim().is_empty() {
+            return Err(ValidationError::MissingField("name".to_string()));
+        }
+
+        if self.steps.is_empty() {
+            return Err(ValidationError::MissingField("steps".to_string()));
+        }
+
+        for step in &self.steps {
+            let path = self.request_path(step);
+            if !path.is_file() {
+                return Err(ValidationError::FileNotFound(format!(
+                    "{} (step '{}')", path.display(), step.id
+                )));
+            }
+        }
+
+        self.execution_order().map(|_| ())
+    }
+
+    /// Orders the steps so every step runs after the steps it depends on.
+    /// Independent steps keep the order they were written in.
+    pub fn execution_order(&self) -> Result<Vec<usize>, ValidationError> {
+        let mut index_by_id = HashMap::new();
+        for (index, step) in self.steps.iter().enumerate() {
+            if step.id.trim().is_empty() {
+                return Err(ValidationError::MissingField(format!("steps[{}].id", index)));
+            }
+            if index_by_id.insert(step.id.as_str(), index).is_some() {
+                return Err(ValidationError::InvalidWorkflow(format!("Duplicate step id '{}'", step.id)));
+            }
+        }
+
+        let mut dependencies: Vec<Vec<usize>> = Vec::with_capacity(self.steps.len());
+        for step in &self.steps {
+            let mut step_dependencies = Vec::new();
+            for dependency in step.depends_on.iter().flatten() {
+                match index_by_id.get(dependency.as_str()) {
+                    Some(index) => step_dependencies.push(*index),
+                    None => {
+                        return Err(ValidationError::InvalidWorkflow(format!(
+                            "Step '{}' depends on missing step '{}'", step.id, dependency
+                        )));
+                    }
+                }
+            }
+            dependencies.push(step_dependencies);
+        }
+
+        let mut order = Vec::with_capacity(self.steps.len());
+        let mut done = HashSet::new();
+        while order.len() < self.steps.len() {
+            let next = (0..self.steps.len()).find(|index| {
+                !done.contains(index) && dependencies[*index].iter().all(|dep| done.contains(dep))
+            });
+
+            match next {
+                Some(index) => {
+                    done.insert(index);
+                    order.push(index);
+                }
+                None => {
+                    let mut remaining: Vec<_> = (0..self.steps.len())
+                        .filter(|index| !done.contains(index))
+                        .map(|index| self.steps[index].id.as_str())
+                        .collect();
+                    remaining.sort();
+                    return Err(ValidationError::InvalidWorkflow(format!(
+                        "Dependency cycle between steps: {}", remaining.join(", ")
+                    )));
+                }
+            }
+        }
+
+        Ok(order)
+    }
+}
+
+pub struct WorkflowRunner;
+
+impl WorkflowRunner {
+    /// Runs the steps of `workflow` one by one in dependency order. Captured
+    /// values are shared with every later step; a step's own `variables`
+    /// apply to that step only and override `--var`. A step whose dependency
+    /// failed is skipped.
+    pub async fn run(
+        workflow: &Workflow,
+        env_resolver: &EnvironmentResolver,
+        executor: &RequestExecutor,
+    ) -> Result<Vec<RunResult>, ValidationError> {
+        let order = workflow.execution_order()?;
+        let mut env_resolver = env_resolver.clone();
+        let mut failed_steps = HashSet::new();
+        let mut results = Vec::new();
+
+        for index in order {
+            let step = &workflow.steps[index];
+            let mut result = RunResult::new(workflow.request_path(step));
+            result.name = Some(step.id.clone());
+
+            let failed_dependency = step.depends_on.iter().flatten()
+                .find(|dependency| failed_steps.contains(dependency.as_str()));
+            if let Some(dependency) = failed_dependency {
+                result.add_error(format!("Skipped because step '{}' failed", dependency));
+                failed_steps.insert(step.id.as_str());
+                results.push(result);
+                continue;
+            }
+
+            match Self::prepare_step(workflow, step, &env_resolver) {
+                Ok(resolved) => {
+                    result.name = Some(format!("{} ({})", step.id, resolved.name));
+                    CollectionRunner::execute_resolved(&mut result, &resolved, executor).await;
+                    for (name, value) in &result.captured {
+                        env_resolver.set_variable(name.clone(), value.clone());
+                    }
+                }
+                Err(error) => result.add_error(error.to_string()),
+            }
+
+            if !result.passed() {
+                failed_steps.insert(step.id.as_str());
+            }
+            results.push(result);
+        }
+
+        Ok(results)
+    }
+
+    fn prepare_step(
+        workflow: &Workflow,
+        step: &WorkflowStep,
+        env_resolver: &EnvironmentResolver,
+    ) -> Result<RequestDefinition, ValidationError> {
+        let mut step_resolver = env_resolver.clone();
+        for (name, value) in step.variables.iter().flatten() {
+            step_resolver.set_step_variable(name.clone(), value.clone());
+        }
+
+        let mut request = RequestParser::parse_file_with_env(workflow.request_path(step), &step_resolver)?;
+
+        if let Some(tests) = &step.tests {
+            request.tests.get_or_insert_with(Vec::new).extend(tests.iter().cloned());
+        }
+
+        request.resolve_with_env(&step_resolver)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::request::OAuth2TokenCache;
+    use std::io::Write;
+    use tempfile::Builder as TempFileBuilder;
+    use wiremock::matchers::{method, path};
+    use wiremock::{Mock, MockServer, ResponseTemplate};
+
+    fn step(id: &str, depends_on: &[&str]) -> WorkflowStep {
+        WorkflowStep {
+            id: id.to_string(),
+            request: format!("{}.yaml", id),
+            depends_on: Some(depends_on.iter().map(|d| d.to_string()).collect()),
+            variables: None,
+            tests: None,
+        }
+    }
+
+    fn workflow(steps: Vec<WorkflowStep>) -> Workflow {
+        Workflow {
+            name: "Test".to_string(),
+            steps,
+            base_dir: PathBuf::new(),
+        }
+    }
+
+    #[test]
+    fn test_execution_order_is_topological_and_stable() {
+        let workflow = workflow(vec![
+            step("delete", &["fetch"]),
+            step("login", &[]),
+            step("fetch", &["create"]),
+            step("create", &["login"]),
+            step("health", &[]),
+        ]);
+
+        let order: Vec<_> = workflow.execution_order().unwrap()
+            .into_iter()
+            .map(|index| workflow.steps[index].id.as_str())
+            .collect();
+        assert_eq!(order, vec!["login", "create", "fetch", "delete", "health"]);
+    }
+
+    #[test]
+    fn test_execution_order_reports_cycles_and_missing_steps() {
+        let cyclic = workflow(vec![step("a", &["b"]), step("b", &["a"]), step("c", &[])]);
+        match cyclic.execution_order() {
+            Err(ValidationError::InvalidWorkflow(message)) => {
+                assert_eq!(message, "Dependency cycle between steps: a, b");
+            }
+            other => panic!("Expected cycle error, got {:?}", other),
+        }
+
+        let missing = workflow(vec![step("a", &["login"])]);
+        match missing.execution_order() {
+            Err(ValidationError::InvalidWorkflow(message)) => assert!(message.contains("missing step 'login'")),
+            other => panic!("Expected missing step error, got {:?}", other),
+        }
+
+        let duplicate = workflow(vec![step("a", &[]), step("a", &[])]);
+        assert!(duplicate.execution_order().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_run_skips_dependents_of_failed_steps() {
+        let server = MockServer::start().await;
+        Mock::given(method("POST"))
+            .and(path("/login"))
+            .respond_with(ResponseTemplate::new(500))
+            .mount(&server)
+            .await;
+        Mock::given(method("GET"))
+            .and(path("/health"))
+            .respond_with(ResponseTemplate::new(200))
+            .mount(&server)
+            .await;
+
+        let temp_dir = TempFileBuilder::new().prefix("test_workflow_").tempdir().unwrap();
+        let dir_path = temp_dir.path();
+        for (file, content) in [
+            ("login.yaml", format!("name: Login\nmethod: POST\nurl: {}/login", server.uri())),
+            ("create.yaml", format!("name: Create\nmethod: POST\nurl: {}/users", server.uri())),
+            ("health.yaml", format!("name: Health\nmethod: GET\nurl: {}/health", server.uri())),
+            ("flow.yaml", "name: Flow\nsteps:\n  - id: login\n    request: login.yaml\n  - id: create\n    request: create.yaml\n    depends_on: [login]\n  - id: health\n    request: health.yaml\n    tests:\n      - status_code: 200".to_string()),
+        ] {
+            let mut file = std::fs::File::create(dir_path.join(file)).unwrap();
+            write!(file, "{}", content).unwrap();
+        }
+
+        let flow_path = dir_path.join("flow.yaml");
+        assert!(Workflow::is_workflow_file(&flow_path));
+        assert!(!Workflow::is_workflow_file(dir_path.join("login.yaml")));
+
+        let workflow = Workflow::load(&flow_path).unwrap();
+        workflow.validate().unwrap();
+
+        let executor = RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory());
+        let results = WorkflowRunner::run(&workflow, &EnvironmentResolver::default(), &executor)
+            .await
+            .unwrap();
+
+        assert_eq!(results.len(), 3);
+        assert!(!results[0].passed());
+        assert!(results[1].response.is_none());
+        assert_eq!(results[1].errors, vec!["Skipped because step 'login' failed".to_string()]);
+        assert!(results[2].passed());
+        assert_eq!(results[2].assertions.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_step_variables_override_cli_variables_for_that_step_only() {
+        let server = MockServer::start().await;
+        for user in ["1", "42"] {
+            Mock::given(method("GET"))
+                .and(path(format!("/users/{}", user)))
+                .respond_with(ResponseTemplate::new(200))
+                .mount(&server)
+                .await;
+        }
+
+        let temp_dir = TempFileBuilder::new().prefix("test_workflow_vars_").tempdir().unwrap();
+        let dir_path = temp_dir.path();
+        std::fs::write(dir_path.join("user.yaml"), "name: User\nmethod: GET\nurl: \"{{base_url}}/users/{{user}}\"").unwrap();
+        std::fs::write(
+            dir_path.join("flow.yaml"),
+            "name: Flow\nsteps:\n  - id: other\n    request: user.yaml\n    variables:\n      user: \"42\"\n  - id: default\n    request: user.yaml",
+        ).unwrap();
+
+        let mut env_resolver = EnvironmentResolver::default();
+        env_resolver.set_cli_variable("base_url", server.uri());
+        env_resolver.set_cli_variable("user", "1");
+
+        let workflow = Workflow::load(dir_path.join("flow.yaml")).unwrap();
+        let executor = RequestExecutor::with_token_cache(OAuth2TokenCache::in_memory());
+        let results = WorkflowRunner::run(&workflow, &env_resolver, &executor).await.unwrap();
+
+        let requested: Vec<_> = server.received_requests().await.unwrap()
+            .into_iter()
+            .map(|request| request.url.path().to_string())
+            .collect();
+        assert_eq!(requested, vec!["/users/42", "/users/1"]);
+        assert!(results.iter().all(|result| result.passed()));
+    }
+}
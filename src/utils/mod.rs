@@ -3,3 +3,6 @@ pub use file_utils::*;
 
 pub mod parsing_utils; 
 pub use parsing_utils::*; 
+
+pub mod encoding_utils;
+pub use encoding_utils::*;
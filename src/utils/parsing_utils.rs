@@ -28,24 +28,34 @@ fn determine_format_from_path(path: &Path, content: &str) -> Result<FileFormat,
 /// * `T`: The type to deserialize the file content into. Must implement `serde::de::DeserializeOwned`.
 /// * `P`: A type that can be converted into a `Path` reference (e.g., `&str`, `PathBuf`).
 pub fn load_and_parse_file<T: DeserializeOwned, P: AsRef<Path>>(path: P) -> Result<T, ValidationError> {
-      let path_ref = path.as_ref(); 
+    let path_ref = path.as_ref();
+    let content = read_file(path_ref)?;
+    parse_file_content(path_ref, &content)
+}
 
-    let content = std::fs::read_to_string(path_ref)
+pub fn read_file<P: AsRef<Path>>(path: P) -> Result<String, ValidationError> {
+    let path_ref = path.as_ref();
+    std::fs::read_to_string(path_ref)
         .with_context(|| format!("Failed to read file {}", path_ref.display()))
-        .map_err(|e: anyhow::Error| ValidationError::FileIo(e.to_string()))?;
+        .map_err(|e: anyhow::Error| ValidationError::FileIo(e.to_string()))
+}
 
-    let format = determine_format_from_path(path_ref, &content)?;
+/// Parses `content` that was read from (or rendered for) `path`; the format
+/// is picked the same way as in `load_and_parse_file`.
+pub fn parse_file_content<T: DeserializeOwned, P: AsRef<Path>>(path: P, content: &str) -> Result<T, ValidationError> {
+    let path_ref = path.as_ref();
+    let format = determine_format_from_path(path_ref, content)?;
 
     match format {
         FileFormat::Yaml => {
-            serde_yaml::from_str(&content)
+            serde_yaml::from_str(content)
                 .with_context(|| format!("Failed to parse YAML content from {}", path_ref.display()))
-                .map_err(|e| ValidationError::FileIo(e.to_string()))
+                .map_err(|e| ValidationError::Parse(format!("{:#}", e)))
         },
         FileFormat::Json => {
-            serde_json::from_str(&content)
+            serde_json::from_str(content)
                 .with_context(|| format!("Failed to parse JSON content from {}", path_ref.display()))
-                .map_err(|e| ValidationError::FileIo(e.to_string()))
+                .map_err(|e| ValidationError::Parse(format!("{:#}", e)))
         }
     }
 }
@@ -91,7 +101,7 @@ mod test {
     fn test_load_and_parse_file_no_extension_json_content() {
         let json_content = r#"{"name": "SniffJSON", "value": 789}"#;
         // Create a temp file without an extension for sniffing
-        let mut temp_file = NamedTempFile::builder().suffix("").tempfile().unwrap();
+        let mut temp_file = tempfile::Builder::new().suffix("").tempfile().unwrap();
         write!(temp_file, "{}", json_content).unwrap();
         temp_file.flush().unwrap();
 
@@ -103,7 +113,7 @@ mod test {
     #[test]
     fn test_load_and_parse_file_no_extension_yaml_content() {
         let yaml_content = "name: SniffYAML\nvalue: 101";
-        let mut temp_file = NamedTempFile::builder().suffix("").tempfile().unwrap();
+        let mut temp_file = tempfile::Builder::new().suffix("").tempfile().unwrap();
         write!(temp_file, "{}", yaml_content).unwrap();
         temp_file.flush().unwrap();
 
@@ -124,7 +134,7 @@ mod test {
 
     #[test]
     fn test_unsupported_format() {
-        let mut temp_file = NamedTempFile::builder().suffix(".txt").tempfile().unwrap();
+        let mut temp_file = tempfile::Builder::new().suffix(".txt").tempfile().unwrap();
         write!(temp_file, "some text").unwrap();
         temp_file.flush().unwrap();
 
@@ -154,7 +164,7 @@ mod test {
      #[test]
     fn test_invalid_json_content() {
         let json_content = r#"{"name": "TestJSON", "value": 456,}"#; // Trailing comma
-        let mut temp_file = NamedTempFile::builder().suffix(".json").tempfile().unwrap();
+        let mut temp_file = tempfile::Builder::new().suffix(".json").tempfile().unwrap();
         write!(temp_file, "{}", json_content).unwrap();
         temp_file.flush().unwrap();
 
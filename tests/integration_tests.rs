@@ -1,6 +1,6 @@
 #[cfg(test)]
 mod tests {
-    use rustman::request::parse_request_file;
+    use rustman::request::RequestParser;
     use tempfile::NamedTempFile;
     use std::io::Write;
 
@@ -17,7 +17,7 @@ headers:
         let mut temp_file = NamedTempFile::new().unwrap();
         temp_file.write_all(yaml_content.as_bytes()).unwrap();
         
-        let result = parse_request_file(temp_file.path());
+        let result = RequestParser::parse_file(temp_file.path());
         assert!(result.is_ok());
         
         let request = result.unwrap();